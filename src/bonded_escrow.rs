@@ -0,0 +1,243 @@
+use scrypto::prelude::*;
+
+use crate::{assert_not_paused, create_escrow_badge, package_governor, EscrowResourceSpecifier};
+
+#[blueprint]
+mod bonded_escrow {
+    struct BondedEscrow {
+        requested_resource: EscrowResourceSpecifier,
+        requested_bond: EscrowResourceSpecifier,
+        offered_resource: Vault,
+        requested_resource_vault: Vault,
+        bond_vault: Vault,
+        dispute_period_seconds: i64,
+        dispute_deadline: Option<Instant>,
+        status: BondStatus,
+        taker_badge: ResourceAddress,
+        escrow_nft: ResourceAddress,
+        // The governor of the package when the escrow was instantiated
+        governor: ComponentAddress,
+    }
+
+    impl BondedEscrow {
+
+        /// The taker must post `requested_bond`, which must be `Fungible`, together with the
+        /// requested resource. The instantiator has `dispute_period_seconds` after the exchange
+        /// to confirm the off-chain delivery or to dispute it. Fails while the governor of the
+        /// package is paused.
+        pub fn instantiate_bonded_escrow(
+            requested_resource: EscrowResourceSpecifier,
+            requested_bond: EscrowResourceSpecifier,
+            offered_resource: Bucket,
+            dispute_period_seconds: i64
+        ) -> (Global<BondedEscrow>, NonFungibleBucket) {
+            assert!(
+                matches!(requested_bond, EscrowResourceSpecifier::Fungible { .. }),
+                "The bond must be a fungible resource!"
+            );
+            assert!(dispute_period_seconds > 0, "The dispute period must be positive!");
+            let governor = package_governor();
+            assert_not_paused(governor);
+
+            let (address_reservation, component_address) =
+                Runtime::allocate_component_address(BondedEscrow::blueprint_id());
+
+            let escrow_nft = create_escrow_badge(offered_resource.resource_address());
+
+            // The taker badge lets the taker reclaim their bond
+            let taker_badge = ResourceBuilder::new_ruid_non_fungible::<TakerBadge>(OwnerRole::None)
+                .metadata(metadata!(
+                    init {
+                        "name" => "Bonded escrow taker badge", locked;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+
+            let escrow = Self {
+                requested_resource_vault: Vault::new(requested_resource.get_resource_address()),
+                bond_vault: Vault::new(requested_bond.get_resource_address()),
+                requested_resource,
+                requested_bond,
+                offered_resource: Vault::with_bucket(offered_resource),
+                dispute_period_seconds,
+                dispute_deadline: None,
+                status: BondStatus::Open,
+                taker_badge: taker_badge.address(),
+                escrow_nft: escrow_nft.resource_address(),
+                governor,
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .with_address(address_reservation)
+            .globalize();
+
+            (escrow, escrow_nft)
+        }
+
+        /// Exchanges the requested resource and the bond for the offered resource. The returned
+        /// taker badge is needed to reclaim the bond.
+        pub fn exchange(&mut self, bucket_of_resource: Bucket, bond: Bucket) -> (Bucket, NonFungibleBucket) {
+            assert_not_paused(self.governor);
+            assert!(
+                self.status == BondStatus::Open,
+                "The offered resource has already been withdrawn!"
+            );
+            self.requested_resource.assert_matches(&bucket_of_resource);
+            self.requested_bond.assert_matches(&bond);
+
+            self.requested_resource_vault.put(bucket_of_resource);
+            self.bond_vault.put(bond);
+            self.status = BondStatus::Taken;
+            self.dispute_deadline = Clock::current_time_rounded_to_seconds()
+                .add_seconds(self.dispute_period_seconds);
+
+            let taker_badge = ResourceManager::from(self.taker_badge)
+                .mint_ruid_non_fungible(TakerBadge {
+                    escrow: Runtime::global_address(),
+                })
+                .as_non_fungible();
+
+            (self.offered_resource.take_all(), taker_badge)
+        }
+
+        /// Confirms the taker delivered, releasing their bond.
+        pub fn confirm_delivery(&mut self, escrow_nft: Proof) {
+            escrow_nft.check(self.escrow_nft);
+            assert!(
+                self.status == BondStatus::Taken,
+                "Only an exchanged escrow can be confirmed!"
+            );
+
+            self.status = BondStatus::Confirmed;
+        }
+
+        /// Disputes the delivery before the dispute deadline, the bond can then be slashed once
+        /// the deadline has passed.
+        pub fn open_dispute(&mut self, escrow_nft: Proof) {
+            escrow_nft.check(self.escrow_nft);
+            assert!(
+                self.status == BondStatus::Taken,
+                "Only an exchanged escrow can be disputed!"
+            );
+            assert!(
+                Clock::current_time_is_strictly_before(self.dispute_deadline.unwrap(), TimePrecision::Second),
+                "The dispute deadline has passed!"
+            );
+
+            self.status = BondStatus::Disputed;
+        }
+
+        /// Returns the bond to the taker once the delivery was confirmed, or once the dispute
+        /// deadline passed without a dispute.
+        pub fn reclaim_bond(&mut self, taker_badge: NonFungibleBucket) -> Bucket {
+            assert_eq!(
+                taker_badge.resource_address(),
+                self.taker_badge,
+                "You must provide the taker badge of this escrow"
+            );
+            let undisputed = self.status == BondStatus::Taken
+                && Clock::current_time_is_at_or_after(self.dispute_deadline.unwrap(), TimePrecision::Second);
+            assert!(
+                self.status == BondStatus::Confirmed || undisputed,
+                "The bond can't be reclaimed until the delivery is confirmed or the dispute deadline passes"
+            );
+
+            self.status = BondStatus::Settled;
+            taker_badge.burn();
+
+            self.bond_vault.take_all()
+        }
+
+        /// Slashes the bond to the instantiator once the dispute deadline of a disputed delivery
+        /// has passed.
+        pub fn slash_bond(&mut self, escrow_nft: Proof) -> Bucket {
+            escrow_nft.check(self.escrow_nft);
+            assert!(
+                self.status == BondStatus::Disputed,
+                "Only a disputed bond can be slashed!"
+            );
+            assert!(
+                Clock::current_time_is_at_or_after(self.dispute_deadline.unwrap(), TimePrecision::Second),
+                "The dispute deadline has not passed yet!"
+            );
+
+            self.status = BondStatus::Settled;
+
+            self.bond_vault.take_all()
+        }
+
+        /// Burns the badge, so the delivery can no longer be confirmed or disputed afterwards.
+        /// A disputed bond must be slashed first, as `slash_bond` needs the badge. An undisputed
+        /// bond stays reclaimable by the taker once the dispute deadline passes.
+        pub fn withdraw_resource(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to withdraw the resource"
+            );
+
+            assert!(
+                !self.requested_resource_vault.is_empty(),
+                "The offer has not been accepted yet, you may want to cancel the escrow instead"
+            );
+            assert!(
+                self.status != BondStatus::Disputed,
+                "The bond is disputed, slash it before withdrawing the resource"
+            );
+
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            self.requested_resource_vault.take_all()
+        }
+
+        pub fn cancel_escrow(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to withdraw the resource"
+            );
+            assert!(
+                self.status == BondStatus::Open,
+                "The offered resource has already been withdrawn!"
+            );
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            self.offered_resource.take_all()
+        }
+
+        pub fn get_status(&self) -> BondStatus {
+            self.status
+        }
+    }
+}
+
+#[derive(ScryptoSbor, ManifestSbor, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BondStatus {
+    /// Waiting for a taker
+    Open,
+    /// Exchanged, the delivery is neither confirmed nor disputed
+    Taken,
+    /// The instantiator confirmed the delivery
+    Confirmed,
+    /// The instantiator disputed the delivery
+    Disputed,
+    /// The bond was reclaimed or slashed
+    Settled
+}
+
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct TakerBadge {
+    pub escrow: GlobalAddress
+}
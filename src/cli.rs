@@ -0,0 +1,337 @@
+//! The command line of the `escrow` binary. Its manifests are built by `generate`, which the
+//! committed manifests test drives with the same arguments as the binary.
+
+use clap::{Parser, Subcommand};
+use radix_transactions::prelude::ManifestBuilder;
+use scrypto::prelude::*;
+
+use crate::manifests::{self, FeeStrategy, ManifestFormat};
+use crate::profiles::{
+    parse_component_address, parse_non_fungible_global_id, parse_package_address, parse_resource_address,
+    parse_resource_specifier, NetworkProfile
+};
+use crate::{EscrowCallback, EscrowOptions, EscrowResourceSpecifier};
+
+/// Writes the transaction manifests of the `Escrow` blueprint to `.rtm` files, or to compiled
+/// and JSON files with `--format`.
+///
+/// Resources are given as `<resource_address>:<amount>` for fungibles, or as
+/// `<resource_address>:<non_fungible_local_id>` for a single non-fungible, e.g.
+/// `resource_tdx_2_1...:#1#`. Addresses left out are taken from the selected network profile.
+/// Manifests calling an escrow the profile describes guarantee what it returns, unless `--expect`
+/// says otherwise.
+#[derive(Parser)]
+#[command(name = "escrow")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// The network profile to generate the manifest for
+    #[arg(long, global = true, default_value = "stokenet")]
+    pub profile: String,
+
+    /// The network profiles file
+    #[arg(long, global = true, default_value = "network_profiles.toml")]
+    pub profiles: String,
+
+    /// Directory the manifest is written to
+    #[arg(long, global = true, default_value = "./transaction_manifest")]
+    pub out_dir: String,
+
+    /// Name of the written files, the method name by default
+    #[arg(long, global = true)]
+    pub name: Option<String>,
+
+    /// The format written, repeat it to write several
+    #[arg(long = "format", global = true, value_enum, default_values_t = [ManifestFormat::Rtm])]
+    pub formats: Vec<ManifestFormat>,
+
+    /// How the transaction fee is locked, the profile's default if left out
+    #[arg(long, global = true, value_enum)]
+    pub fee: Option<FeeStrategy>,
+
+    /// The XRD amount locked from an account for the fee
+    #[arg(long, global = true)]
+    pub fee_amount: Option<String>,
+
+    /// The account paying the fee, the account of the operation by default
+    #[arg(long, global = true)]
+    pub fee_account: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Offers a resource in exchange for any one of the requested resources
+    InstantiateEscrow {
+        /// The instantiator's account, which receives the escrow badge
+        #[arg(long)]
+        account: Option<String>,
+        #[arg(long)]
+        package: Option<String>,
+        /// The resource put in escrow
+        #[arg(long)]
+        offered: String,
+        /// A requested resource, repeat it to list alternatives
+        #[arg(long, required = true)]
+        requested: Vec<String>,
+        /// The account receiving the proceeds on `exchange`, instead of `withdraw_resource`
+        #[arg(long)]
+        settlement_account: Option<String>,
+        /// Stores the proceeds in an account locker for the settlement account to claim
+        #[arg(long, requires = "settlement_account")]
+        use_account_locker: bool,
+        /// The `<component_address>:<method>` called once the escrow is filled
+        #[arg(long)]
+        callback: Option<String>,
+        /// Mints a taker receipt to every taker
+        #[arg(long)]
+        mint_taker_receipt: bool,
+        /// A referrer badge and its share of the proceeds, `<resource_address>:<local_id>:<share>`,
+        /// repeat it to list several
+        #[arg(long = "referrer")]
+        referrers: Vec<String>,
+        /// The XRD deposit withdrawn from the account, returned by `withdraw_resource`
+        #[arg(long)]
+        creation_deposit: Option<String>,
+    },
+    /// Pays one of the requested resources and takes the offered resource
+    Exchange {
+        /// The taker's account
+        #[arg(long)]
+        account: Option<String>,
+        #[arg(long)]
+        component: Option<String>,
+        /// The requested resource paid by the taker
+        #[arg(long)]
+        paid: String,
+        /// Fails the exchange if the terms have been updated since this revision
+        #[arg(long)]
+        terms_revision: Option<u64>,
+        /// The offered resource the taker must receive, enforced by the manifest. The profile's
+        /// description of the escrow gives it by default
+        #[arg(long)]
+        expect: Option<String>,
+        /// The referrer badge, `<resource_address>:<local_id>`, earning its share of the proceeds
+        #[arg(long)]
+        referrer: Option<String>,
+    },
+    /// Withdraws the proceeds of a filled escrow, burning the escrow badge
+    WithdrawResource {
+        #[command(flatten)]
+        badge: BadgeArgs,
+    },
+    /// Cancels an escrow and takes back the offered resource, burning the escrow badge
+    CancelEscrow {
+        #[command(flatten)]
+        badge: BadgeArgs,
+    },
+    /// Prints what an `.rtm` manifest of the profile's network does, instead of writing one
+    Explain {
+        /// The `.rtm` file
+        manifest: String,
+    },
+    /// Writes a resim shell script of an end-to-end demo to `<out-dir>/<name>.sh`, instead of a
+    /// manifest
+    ResimScript {
+        /// The amount of the token offered by the first account
+        #[arg(long, default_value = "100")]
+        offered_amount: String,
+        /// The amount of the token requested from the second account
+        #[arg(long, default_value = "50")]
+        requested_amount: String,
+    },
+    /// Executes an `.rtm` manifest of the profile's network on a simulated ledger, instead of
+    /// writing one
+    #[cfg(feature = "dry-run")]
+    DryRun {
+        /// The `.rtm` file
+        manifest: String,
+        /// The accounts, resources and escrows the manifest references
+        #[arg(long, default_value = "dry_run_fixture.toml")]
+        fixture: String,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct BadgeArgs {
+    /// The instantiator's account, holding the escrow badge
+    #[arg(long)]
+    pub account: Option<String>,
+    #[arg(long)]
+    pub component: Option<String>,
+    /// The escrow badge resource
+    #[arg(long)]
+    pub badge: Option<String>,
+    #[arg(long, default_value = "#1#")]
+    pub badge_id: String,
+    /// The resource the instantiator must receive, enforced by the manifest. The profile's
+    /// description of the escrow gives it by default
+    #[arg(long)]
+    pub expect: Option<String>,
+}
+
+/// Builds the manifest of an `instantiate-escrow`, `exchange`, `withdraw-resource` or
+/// `cancel-escrow` command, with the addresses, fee and guarantees left out taken from the
+/// profile. Returns it with the name of its files, `--name` or the method name.
+pub fn generate(cli: &Cli, profile: &NetworkProfile) -> (ManifestBuilder, String) {
+    let decoder = AddressBech32Decoder::new(&profile.network);
+    let fee_account = cli.fee_account.as_ref().map(|account| parse_component_address(&decoder, account));
+    let fee_amount = cli.fee_amount
+        .as_ref()
+        .map(|amount| Decimal::from_str(amount).expect("Invalid fee amount"))
+        .unwrap_or(profile.fee_amount);
+    let fee = cli.fee.unwrap_or(profile.fee);
+    // The wallet submitting a `sendTransaction` request adds its own fee lock
+    assert!(
+        fee == FeeStrategy::None || !cli.formats.contains(&ManifestFormat::Json),
+        "The wallet locks the fee of a json manifest, pass --fee none"
+    );
+    let start_manifest = |account: ComponentAddress| {
+        manifests::lock_fee(ManifestBuilder::new(), fee, fee_account.unwrap_or(account), fee_amount)
+    };
+
+    let (manifest, method) = match &cli.command {
+        Command::InstantiateEscrow {
+            account,
+            package,
+            offered,
+            requested,
+            settlement_account,
+            use_account_locker,
+            callback,
+            mint_taker_receipt,
+            referrers,
+            creation_deposit
+        } => {
+            let account = account_or_default(&decoder, account.as_deref(), profile);
+            let manifest = start_manifest(account);
+            let package_address = package
+                .as_ref()
+                .map(|package| parse_package_address(&decoder, package))
+                .or(profile.package)
+                .expect("Missing --package, and the profile has no package");
+            let offered = parse_resource_specifier(&decoder, offered);
+            let requested: Vec<EscrowResourceSpecifier> = requested
+                .iter()
+                .map(|requested| parse_resource_specifier(&decoder, requested))
+                .collect();
+            let options = EscrowOptions {
+                settlement_account: settlement_account
+                    .as_ref()
+                    .map(|settlement_account| parse_component_address(&decoder, settlement_account)),
+                use_account_locker: *use_account_locker,
+                callback: callback.as_ref().map(|callback| parse_callback(&decoder, callback)),
+                mint_taker_receipt: *mint_taker_receipt,
+                referrers: referrers
+                    .iter()
+                    .map(|referrer| {
+                        let (badge, share) = referrer
+                            .rsplit_once(':')
+                            .unwrap_or_else(|| panic!("Invalid referrer {}, expected <resource_address>:<local_id>:<share>", referrer));
+                        (parse_non_fungible_global_id(&decoder, badge), Decimal::from_str(share).expect("Invalid referral share"))
+                    })
+                    .collect(),
+            };
+
+            let manifest = manifests::instantiate_escrow_with_deposit(
+                manifest,
+                account,
+                package_address,
+                &offered,
+                requested,
+                options,
+                creation_deposit
+                    .as_ref()
+                    .map(|amount| Decimal::from_str(amount).expect("Invalid creation deposit"))
+            );
+            (manifest, "instantiate_escrow")
+        }
+        Command::Exchange { account, component, paid, terms_revision, expect, referrer } => {
+            let account = account_or_default(&decoder, account.as_deref(), profile);
+            let manifest = start_manifest(account);
+            let component_address = component_or_default(&decoder, component.as_deref(), profile);
+            let paid = parse_resource_specifier(&decoder, paid);
+            let expect = expect
+                .as_ref()
+                .map(|expect| parse_resource_specifier(&decoder, expect))
+                .or_else(|| profile.escrow(component_address).map(|escrow| escrow.offered.clone()));
+
+            let referrer = referrer.as_ref().map(|referrer| parse_non_fungible_global_id(&decoder, referrer));
+
+            let manifest = manifests::exchange_with_referrer(
+                manifest,
+                account,
+                component_address,
+                &paid,
+                *terms_revision,
+                referrer,
+                expect.as_ref()
+            );
+            (manifest, "exchange")
+        }
+        Command::WithdrawResource { badge } => {
+            let (account, component_address, badge_address, badge_id, expect) = parse_badge_args(&decoder, badge, profile);
+            let expect = expect.or_else(|| profile.escrow(component_address).and_then(|escrow| escrow.withdraw_guarantee()));
+            let manifest = start_manifest(account);
+            let manifest = manifests::withdraw_resource(manifest, account, component_address, badge_address, badge_id, expect.as_ref());
+            (manifest, "withdraw_resource")
+        }
+        Command::CancelEscrow { badge } => {
+            let (account, component_address, badge_address, badge_id, expect) = parse_badge_args(&decoder, badge, profile);
+            let expect = expect.or_else(|| profile.escrow(component_address).map(|escrow| escrow.offered.clone()));
+            let manifest = start_manifest(account);
+            let manifest = manifests::cancel_escrow(manifest, account, component_address, badge_address, badge_id, expect.as_ref());
+            (manifest, "cancel_escrow")
+        }
+        Command::Explain { .. } | Command::ResimScript { .. } => panic!("The command writes no manifest"),
+        #[cfg(feature = "dry-run")]
+        Command::DryRun { .. } => panic!("The command writes no manifest"),
+    };
+
+    (manifest, cli.name.clone().unwrap_or_else(|| method.to_string()))
+}
+
+fn parse_badge_args(
+    decoder: &AddressBech32Decoder,
+    badge: &BadgeArgs,
+    profile: &NetworkProfile
+) -> (ComponentAddress, ComponentAddress, ResourceAddress, NonFungibleLocalId, Option<EscrowResourceSpecifier>) {
+    let account = account_or_default(decoder, badge.account.as_deref(), profile);
+    let component_address = component_or_default(decoder, badge.component.as_deref(), profile);
+    let badge_address = badge.badge
+        .as_ref()
+        .map(|badge| parse_resource_address(decoder, badge))
+        .or(profile.badge)
+        .expect("Missing --badge, and the profile has no badge");
+    let badge_id = NonFungibleLocalId::from_str(&badge.badge_id)
+        .expect("Invalid badge ID");
+    let expect = badge.expect.as_ref().map(|expect| parse_resource_specifier(decoder, expect));
+
+    (account, component_address, badge_address, badge_id, expect)
+}
+
+fn account_or_default(decoder: &AddressBech32Decoder, account: Option<&str>, profile: &NetworkProfile) -> ComponentAddress {
+    account
+        .map(|account| parse_component_address(decoder, account))
+        .or(profile.account)
+        .expect("Missing --account, and the profile has no account")
+}
+
+fn component_or_default(decoder: &AddressBech32Decoder, component: Option<&str>, profile: &NetworkProfile) -> ComponentAddress {
+    component
+        .map(|component| parse_component_address(decoder, component))
+        .or(profile.component)
+        .expect("Missing --component, and the profile has no component")
+}
+
+// Parses `<component_address>:<method>`
+fn parse_callback(decoder: &AddressBech32Decoder, callback: &str) -> EscrowCallback {
+    let (component, method) = callback
+        .split_once(':')
+        .unwrap_or_else(|| panic!("Invalid callback {}, expected <component_address>:<method>", callback));
+
+    EscrowCallback {
+        component: parse_component_address(decoder, component),
+        method: method.to_string()
+    }
+}
@@ -0,0 +1,306 @@
+//! Local dry runs of transaction manifests, used by `escrow dry-run` to preview a manifest before
+//! submitting it. A `LedgerSimulator` is booted, this package is published and the entities the
+//! manifest references are recreated from a `Fixture`, then the manifest is executed with the
+//! signatures of every fixture account. Only built with the `dry-run` feature.
+
+use radix_transactions::manifest::decompiler::decompile;
+use radix_transactions::manifest::{compile, BlobProvider};
+use scrypto::prelude::*;
+use scrypto_test::prelude::*;
+use serde::Deserialize;
+
+use crate::{manifests, EscrowOptions, EscrowResourceSpecifier};
+
+/// The entities a manifest references, by their address on the manifest's network.
+#[derive(Deserialize)]
+pub struct Fixture {
+    /// The address of this package
+    pub package: Option<String>,
+    #[serde(default)]
+    pub accounts: Vec<FixtureAccount>,
+    #[serde(default)]
+    pub resources: Vec<FixtureResource>,
+    #[serde(default)]
+    pub escrows: Vec<FixtureEscrow>,
+}
+
+/// An account, allocated with 10000 XRD.
+#[derive(Deserialize)]
+pub struct FixtureAccount {
+    pub address: String,
+}
+
+/// A resource minted to the `owner` account, fungible with a `supply` or non-fungible with `ids`.
+#[derive(Deserialize)]
+pub struct FixtureResource {
+    pub address: String,
+    pub owner: String,
+    pub supply: Option<String>,
+    pub divisibility: Option<u8>,
+    pub ids: Option<Vec<String>>,
+}
+
+/// An escrow instantiated by the `owner` account, whose escrow badge resource is `badge`.
+/// Resources are written `<resource_address>:<amount or ID>`.
+#[derive(Deserialize)]
+pub struct FixtureEscrow {
+    pub address: String,
+    pub badge: Option<String>,
+    pub owner: String,
+    pub offered: String,
+    pub requested: Vec<String>,
+}
+
+impl Fixture {
+
+    pub fn load(path: &str) -> Self {
+        let fixture = std::fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Failed to read the fixture {}: {}", path, error));
+        toml::from_str(&fixture)
+            .unwrap_or_else(|error| panic!("Invalid fixture {}: {}", path, error))
+    }
+}
+
+/// What executing a manifest did, with the addresses of the manifest's network.
+pub struct DryRunReport {
+    /// Whether the fee was locked from the faucet, as the manifest leaves it to the wallet
+    pub faucet_fee_added: bool,
+    pub success: bool,
+    pub outcome: String,
+    pub fees: Vec<(&'static str, Decimal)>,
+    /// Account, resource and balance change, for every balance of a fixture account that changed
+    pub balance_changes: Vec<(String, String, Decimal)>,
+}
+
+#[derive(ScryptoSbor, ManifestSbor, NonFungibleData)]
+struct FixtureNonFungible {}
+
+/// Recreates the fixture on a simulated ledger and executes the manifest text of the network.
+pub fn dry_run(manifest: &str, network: &NetworkDefinition, fixture: &Fixture) -> DryRunReport {
+    let decoder = AddressBech32Decoder::new(network);
+    let simulator = NetworkDefinition::simulator();
+    let simulator_encoder = AddressBech32Encoder::new(&simulator);
+    let mut ledger = LedgerSimulatorBuilder::new().build();
+
+    // Simulated entities by their address on the manifest's network
+    let mut accounts: IndexMap<String, (Secp256k1PublicKey, ComponentAddress)> = index_map_new();
+    let mut addresses: IndexMap<String, GlobalAddress> = index_map_new();
+
+    for account in &fixture.accounts {
+        let (public_key, _private_key, address) = ledger.new_allocated_account();
+        accounts.insert(account.address.clone(), (public_key, address));
+        addresses.insert(account.address.clone(), address.into());
+    }
+    let account = |address: &str| accounts
+        .get(address)
+        .copied()
+        .unwrap_or_else(|| panic!("The account {} is not in the fixture", address));
+
+    if let Some(package) = &fixture.package {
+        // Escrows can only be instantiated once the package owner named their governor
+        let (public_key, _private_key, owner) = ledger.new_allocated_account();
+        let owner_badge = NonFungibleGlobalId::from_public_key(&public_key);
+        let package_address = ledger.compile_and_publish_with_owner(this_package!(), owner_badge.clone());
+        let manifest = manifests::instantiate_escrow_governor(
+            ManifestBuilder::new().lock_fee_from_faucet(),
+            owner,
+            package_address
+        )
+        .build();
+        let receipt = ledger.execute_manifest(manifest, vec![owner_badge.clone()]);
+        let governor = receipt.expect_commit_success().new_component_addresses()[0];
+        let manifest = manifests::set_package_governor(
+            ManifestBuilder::new().lock_fee_from_faucet(),
+            package_address,
+            governor
+        )
+        .build();
+        ledger.execute_manifest(manifest, vec![owner_badge]).expect_commit_success();
+        addresses.insert(package.clone(), package_address.into());
+    }
+
+    for resource in &fixture.resources {
+        let (public_key, owner) = account(&resource.owner);
+        let builder = ManifestBuilder::new().lock_fee_from_faucet();
+        let builder = match (&resource.supply, &resource.ids) {
+            (Some(supply), None) => builder.create_fungible_resource(
+                OwnerRole::None,
+                true,
+                resource.divisibility.unwrap_or(DIVISIBILITY_MAXIMUM),
+                FungibleResourceRoles::default(),
+                metadata!(),
+                Some(Decimal::from_str(supply).expect("Invalid supply")),
+            ),
+            (None, Some(ids)) => {
+                let ids: Vec<NonFungibleLocalId> = ids
+                    .iter()
+                    .map(|id| NonFungibleLocalId::from_str(id).expect("Invalid non-fungible ID"))
+                    .collect();
+                builder.create_non_fungible_resource(
+                    OwnerRole::None,
+                    ids.first().expect("A non-fungible resource needs ids").id_type(),
+                    true,
+                    NonFungibleResourceRoles::default(),
+                    metadata!(),
+                    Some(ids.into_iter().map(|id| (id, FixtureNonFungible {}))),
+                )
+            }
+            _ => panic!("The resource {} needs either a supply or ids", resource.address),
+        };
+        let manifest = builder.try_deposit_entire_worktop_or_abort(owner, None).build();
+        let receipt = ledger.execute_manifest(manifest, vec![NonFungibleGlobalId::from_public_key(&public_key)]);
+        let created = receipt.expect_commit_success().new_resource_addresses()[0];
+        addresses.insert(resource.address.clone(), created.into());
+    }
+
+    let package_address = fixture.package
+        .as_ref()
+        .map(|package| PackageAddress::try_from(addresses[package].as_node_id().0).unwrap());
+    for escrow in &fixture.escrows {
+        let (public_key, owner) = account(&escrow.owner);
+        let specifier = |specifier: &str| simulated_specifier(specifier, &decoder, &simulator_encoder, &addresses);
+        let manifest = manifests::instantiate_escrow(
+            ManifestBuilder::new().lock_fee_from_faucet(),
+            owner,
+            package_address.expect("Escrows need the package in the fixture"),
+            &specifier(&escrow.offered),
+            escrow.requested.iter().map(|requested| specifier(requested)).collect(),
+            EscrowOptions::default(),
+        )
+        .build();
+        let receipt = ledger.execute_manifest(manifest, vec![NonFungibleGlobalId::from_public_key(&public_key)]);
+        let result = receipt.expect_commit_success();
+        let component = result
+            .new_component_addresses()
+            .iter()
+            .find(|address| address.as_node_id().entity_type() == Some(EntityType::GlobalGenericComponent))
+            .copied()
+            .unwrap();
+        addresses.insert(escrow.address.clone(), component.into());
+        if let Some(badge) = &escrow.badge {
+            addresses.insert(badge.clone(), result.new_resource_addresses()[0].into());
+        }
+    }
+
+    // Moves the manifest to the simulator network, then swaps the fixture addresses for the
+    // simulated entities
+    let compiled = compile(manifest, network, BlobProvider::new())
+        .unwrap_or_else(|error| panic!("Invalid manifest for network {}: {:?}", network.logical_name, error));
+    let mut text = decompile(&compiled.instructions, &simulator).expect("Failed to decompile the manifest");
+    let faucet_fee_added = !compiled.instructions.iter().any(|instruction| matches!(
+        instruction,
+        InstructionV1::CallMethod { method_name, .. } if method_name.starts_with("lock_")
+    ));
+    if faucet_fee_added {
+        let lock_fee = ManifestBuilder::new().lock_fee_from_faucet().build();
+        let lock_fee = decompile(&lock_fee.instructions, &simulator).expect("Failed to decompile the manifest");
+        text = lock_fee + &text;
+    }
+    for (address, simulated) in &addresses {
+        text = text.replace(
+            &format!("\"{}\"", simulator_address(address, &decoder, &simulator_encoder)),
+            &format!("\"{}\"", encode(&simulator_encoder, simulated)),
+        );
+    }
+    let blobs: Vec<Vec<u8>> = compiled.blobs.values().cloned().collect();
+    let simulated_manifest = compile(&text, &simulator, BlobProvider::new_with_blobs(blobs))
+        .expect("Failed to compile the simulated manifest");
+
+    // The balances of every fixture account in XRD and the fixture resources, badges included
+    let mut resources: Vec<(String, ResourceAddress)> = vec![("XRD".to_string(), XRD)];
+    for (address, simulated) in &addresses {
+        if let Ok(resource_address) = ResourceAddress::try_from(simulated.as_node_id().0) {
+            resources.push((address.clone(), resource_address));
+        }
+    }
+    let mut balances: Vec<(String, String, ComponentAddress, ResourceAddress)> = Vec::new();
+    for (account_name, (_, account_address)) in &accounts {
+        for (resource_name, resource_address) in &resources {
+            balances.push((account_name.clone(), resource_name.clone(), *account_address, *resource_address));
+        }
+    }
+
+    let before: Vec<Decimal> = balances
+        .iter()
+        .map(|(_, _, account, resource)| ledger.get_component_balance(*account, *resource))
+        .collect();
+    let signers: Vec<NonFungibleGlobalId> = accounts
+        .values()
+        .map(|(public_key, _)| NonFungibleGlobalId::from_public_key(public_key))
+        .collect();
+    let receipt = ledger.execute_manifest(simulated_manifest, signers);
+
+    let (success, outcome) = match &receipt.result {
+        TransactionResult::Commit(commit) => match &commit.outcome {
+            TransactionOutcome::Success(_) => (true, "Committed successfully".to_string()),
+            TransactionOutcome::Failure(error) => (false, format!("Committed with a failure: {:?}", error)),
+        },
+        TransactionResult::Reject(reject) => (false, format!("Rejected: {:?}", reject.reason)),
+        TransactionResult::Abort(abort) => (false, format!("Aborted: {:?}", abort.reason)),
+    };
+
+    let fee_summary = &receipt.fee_summary;
+    let fees = vec![
+        ("Execution", fee_summary.total_execution_cost_in_xrd),
+        ("Finalization", fee_summary.total_finalization_cost_in_xrd),
+        ("Storage", fee_summary.total_storage_cost_in_xrd),
+        ("Royalties", fee_summary.total_royalty_cost_in_xrd),
+        ("Tip", fee_summary.total_tipping_cost_in_xrd),
+        ("Total", fee_summary.total_cost()),
+    ];
+
+    let balance_changes = balances
+        .into_iter()
+        .zip(before)
+        .filter_map(|((account_name, resource_name, account, resource), before)| {
+            let change = ledger.get_component_balance(account, resource) - before;
+            (change != Decimal::ZERO).then_some((account_name, resource_name, change))
+        })
+        .collect();
+
+    DryRunReport { faucet_fee_added, success, outcome, fees, balance_changes }
+}
+
+// Parses `<resource_address>:<amount or ID>`, the resource being a fixture resource or XRD
+fn simulated_specifier(
+    specifier: &str,
+    decoder: &AddressBech32Decoder,
+    simulator_encoder: &AddressBech32Encoder,
+    addresses: &IndexMap<String, GlobalAddress>
+) -> EscrowResourceSpecifier {
+    let (address, amount_or_id) = specifier
+        .split_once(':')
+        .unwrap_or_else(|| panic!("Invalid resource {}, expected <resource_address>:<amount or ID>", specifier));
+    let resource_address = match addresses.get(address) {
+        Some(simulated) => ResourceAddress::try_from(simulated.as_node_id().0).unwrap(),
+        None => {
+            let address = simulator_address(address, decoder, simulator_encoder);
+            ResourceAddress::try_from_bech32(&AddressBech32Decoder::new(&NetworkDefinition::simulator()), &address)
+                .unwrap_or_else(|| panic!("The resource {} is not in the fixture", specifier))
+        }
+    };
+
+    if resource_address.is_fungible() {
+        EscrowResourceSpecifier::Fungible {
+            resource_address,
+            amount: Decimal::from_str(amount_or_id).expect("Invalid amount")
+        }
+    } else {
+        EscrowResourceSpecifier::NonFungible {
+            resource_address,
+            non_fungible_local_id: NonFungibleLocalId::from_str(amount_or_id).expect("Invalid non-fungible ID")
+        }
+    }
+}
+
+// The same entity address, encoded for the simulator network
+fn simulator_address(address: &str, decoder: &AddressBech32Decoder, simulator_encoder: &AddressBech32Encoder) -> String {
+    let (_, bytes) = decoder
+        .validate_and_decode(address)
+        .unwrap_or_else(|_| panic!("Invalid address {} for this network", address));
+    simulator_encoder.encode(&bytes).expect("Failed to encode the address")
+}
+
+fn encode(encoder: &AddressBech32Encoder, address: &GlobalAddress) -> String {
+    encoder.encode(address.as_node_id().as_bytes()).expect("Failed to encode the address")
+}
@@ -0,0 +1,233 @@
+use scrypto::prelude::*;
+
+use crate::{assert_not_paused, create_escrow_badge, package_governor};
+
+#[blueprint]
+mod english_auction {
+    struct EnglishAuction {
+        lot: Vault,
+        reserve_price: Decimal,
+        min_increment: Decimal,
+        end: Instant,
+        top_bid: Vault,
+        top_bid_receipt: Option<NonFungibleLocalId>,
+        top_bidder_account: Option<Global<Account>>,
+        claimable: KeyValueStore<NonFungibleLocalId, Vault>,
+        bid_receipt: ResourceAddress,
+        escrow_nft: ResourceAddress,
+        closed: bool,
+        // The governor of the package when the auction was instantiated
+        governor: ComponentAddress,
+    }
+
+    impl EnglishAuction {
+
+        /// Bids are accepted until `end`, after which anyone can close the auction. Fails while the
+        /// governor of the package is paused.
+        pub fn instantiate_english_auction(
+            lot: Bucket,
+            bid_resource: ResourceAddress,
+            reserve_price: Decimal,
+            min_increment: Decimal,
+            end: Instant
+        ) -> (Global<EnglishAuction>, NonFungibleBucket) {
+            assert!(!lot.is_empty(), "You must put a resource up for auction!");
+            assert!(bid_resource.is_fungible(), "Bids must be made in a fungible resource!");
+            assert!(reserve_price >= Decimal::ZERO, "The reserve price cannot be negative!");
+            assert!(min_increment > Decimal::ZERO, "The minimum increment must be positive!");
+            assert!(
+                Clock::current_time_is_strictly_before(end, TimePrecision::Second),
+                "The end of the auction must be in the future!"
+            );
+            let governor = package_governor();
+            assert_not_paused(governor);
+
+            let (address_reservation, component_address) =
+                Runtime::allocate_component_address(EnglishAuction::blueprint_id());
+
+            // The badge holder is the seller, allowed to collect the proceeds
+            let escrow_nft = create_escrow_badge(lot.resource_address());
+
+            // Every bid is acknowledged with a receipt, used to claim refunds or the lot
+            let bid_receipt = ResourceBuilder::new_ruid_non_fungible::<AuctionBidReceipt>(OwnerRole::None)
+                .metadata(metadata!(
+                    init {
+                        "name" => "Escrow auction bid receipt", locked;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+
+            let auction = Self {
+                lot: Vault::with_bucket(lot),
+                reserve_price,
+                min_increment,
+                end,
+                top_bid: Vault::new(bid_resource),
+                top_bid_receipt: None,
+                top_bidder_account: None,
+                claimable: KeyValueStore::new(),
+                bid_receipt: bid_receipt.address(),
+                escrow_nft: escrow_nft.resource_address(),
+                closed: false,
+                governor,
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .with_address(address_reservation)
+            .globalize();
+
+            (auction, escrow_nft)
+        }
+
+        /// Places a bid, refunding the previous top bidder. The refund is deposited to the
+        /// `refund_account` they gave when bidding, or kept claimable against their bid receipt
+        /// if they gave none or their account rejects the deposit.
+        pub fn bid(&mut self, bid: Bucket, refund_account: Option<Global<Account>>) -> NonFungibleBucket {
+            assert_not_paused(self.governor);
+            assert!(
+                Clock::current_time_is_strictly_before(self.end, TimePrecision::Second),
+                "The auction is over!"
+            );
+            assert_eq!(
+                bid.resource_address(),
+                self.top_bid.resource_address(),
+                "You must bid with the requested resource, invalid resource specified!"
+            );
+            assert!(bid.amount() > Decimal::ZERO, "You must bid a positive amount!");
+            assert!(
+                bid.amount() >= self.minimum_bid(),
+                "Your bid must be at least {}!",
+                self.minimum_bid()
+            );
+
+            // Refund the bidder we are replacing
+            if let Some(receipt_id) = self.top_bid_receipt.take() {
+                let refund = self.top_bid.take_all();
+                let account = self.top_bidder_account.take();
+                self.deliver(receipt_id, account, refund);
+            }
+
+            let amount = bid.amount();
+            self.top_bid.put(bid);
+
+            let receipt = ResourceManager::from(self.bid_receipt)
+                .mint_ruid_non_fungible(AuctionBidReceipt { amount })
+                .as_non_fungible();
+            self.top_bid_receipt = Some(receipt.non_fungible_local_id());
+            self.top_bidder_account = refund_account;
+
+            receipt
+        }
+
+        /// Closes the auction once it is over, which anyone can do so the top bidder is never
+        /// left waiting on the seller. If a bid was placed the lot is settled to the top bidder,
+        /// and the winning bid is kept for the badge holder.
+        pub fn close_auction(&mut self) {
+            assert!(
+                Clock::current_time_is_at_or_after(self.end, TimePrecision::Second),
+                "The auction is not over yet!"
+            );
+            assert!(!self.closed, "The auction has already been closed!");
+
+            self.close();
+        }
+
+        /// Returns the winning bid, or the lot if nobody bid, closing the auction first if nobody
+        /// has yet.
+        pub fn withdraw_proceeds(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to withdraw the proceeds"
+            );
+            assert!(
+                Clock::current_time_is_at_or_after(self.end, TimePrecision::Second),
+                "The auction is not over yet!"
+            );
+
+            if !self.closed {
+                self.close();
+            }
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            // The lot is left only if nobody bid
+            if self.lot.is_empty() {
+                self.top_bid.take_all()
+            } else {
+                self.lot.take_all()
+            }
+        }
+
+        /// Claims whatever is held for a bid receipt: the refund of an outbid bid, or the lot for
+        /// the winning bid.
+        pub fn claim(&mut self, bid_receipt: NonFungibleBucket) -> Bucket {
+            assert_eq!(
+                bid_receipt.resource_address(),
+                self.bid_receipt,
+                "You must provide a bid receipt of this auction"
+            );
+            let receipt_id = bid_receipt.non_fungible_local_id();
+            assert!(
+                self.top_bid_receipt.as_ref() != Some(&receipt_id),
+                "Your bid is still the top bid, wait for the auction to close"
+            );
+
+            let claimed = match self.claimable.get_mut(&receipt_id) {
+                Some(mut vault) => vault.take_all(),
+                None => panic!("Nothing to claim, your refund was deposited to your account"),
+            };
+
+            bid_receipt.burn();
+
+            claimed
+        }
+
+        pub fn get_minimum_bid(&self) -> Decimal {
+            self.minimum_bid()
+        }
+
+        // Settles the lot to the top bidder, if any
+        fn close(&mut self) {
+            self.closed = true;
+            if let Some(receipt_id) = self.top_bid_receipt.take() {
+                let lot = self.lot.take_all();
+                let account = self.top_bidder_account.take();
+                self.deliver(receipt_id, account, lot);
+            }
+        }
+
+        fn minimum_bid(&self) -> Decimal {
+            if self.top_bid_receipt.is_some() {
+                self.top_bid.amount() + self.min_increment
+            } else {
+                self.reserve_price
+            }
+        }
+
+        // Sends a bucket to the bidder's account, keeping it claimable if that is not possible
+        fn deliver(&mut self, receipt_id: NonFungibleLocalId, account: Option<Global<Account>>, bucket: Bucket) {
+            let bucket = match account {
+                Some(mut account) => account.try_deposit_or_refund(bucket, None),
+                None => Some(bucket),
+            };
+            if let Some(bucket) = bucket {
+                self.claimable.insert(receipt_id, Vault::with_bucket(bucket));
+            }
+        }
+    }
+}
+
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct AuctionBidReceipt {
+    pub amount: Decimal
+}
@@ -0,0 +1,69 @@
+use scrypto::prelude::*;
+
+/// Lets an admin pause every escrow and auction of the package, once the package owner names this
+/// component in the `governor` package metadata. A paused governor blocks new instantiations and
+/// the methods taking or funding a deal, like `exchange`, `bid` or `collect`, while the methods
+/// users exit with, like `cancel_escrow` or `claim`, stay open. The governor also receives the
+/// creation deposits forfeited by early cancellations.
+#[blueprint]
+mod escrow_governor {
+    struct EscrowGovernor {
+        admin_badge: ResourceAddress,
+        paused: bool,
+        // XRD forfeited by escrows cancelled early, until the admin withdraws it
+        forfeited_deposits: Vault,
+    }
+
+    impl EscrowGovernor {
+
+        pub fn instantiate_escrow_governor() -> (Global<EscrowGovernor>, FungibleBucket) {
+            let admin_badge = ResourceBuilder::new_fungible(OwnerRole::None)
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata(metadata!(
+                    init {
+                        "name" => "Escrow governor admin badge", locked;
+                    }
+                ))
+                .mint_initial_supply(1);
+
+            let governor = Self {
+                admin_badge: admin_badge.resource_address(),
+                paused: false,
+                forfeited_deposits: Vault::new(XRD),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize();
+
+            (governor, admin_badge)
+        }
+
+        pub fn pause(&mut self, admin_badge: Proof) {
+            admin_badge.check(self.admin_badge);
+            self.paused = true;
+        }
+
+        pub fn unpause(&mut self, admin_badge: Proof) {
+            admin_badge.check(self.admin_badge);
+            self.paused = false;
+        }
+
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Called by escrows cancelled within their minimum open period.
+        pub fn deposit_forfeited(&mut self, forfeited: Bucket) {
+            self.forfeited_deposits.put(forfeited);
+        }
+
+        pub fn withdraw_forfeited_deposits(&mut self, admin_badge: Proof) -> Bucket {
+            admin_badge.check(self.admin_badge);
+            self.forfeited_deposits.take_all()
+        }
+
+        pub fn get_forfeited_deposits(&self) -> Decimal {
+            self.forfeited_deposits.amount()
+        }
+    }
+}
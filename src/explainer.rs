@@ -0,0 +1,238 @@
+//! Plain descriptions of what a transaction manifest does, used by `escrow explain` to review
+//! `.rtm` files. Calls to the `Escrow` blueprint are decoded, including their
+//! `EscrowResourceSpecifier` arguments, along with the account and worktop instructions the
+//! `manifests` module generates. Calls whose arguments don't match, like the `exchange` of the
+//! other escrow blueprints, are described by their method name, and other instructions are
+//! printed as they are.
+
+use radix_transactions::manifest::{compile, BlobProvider};
+use radix_transactions::prelude::*;
+use scrypto::prelude::*;
+
+use crate::{EscrowOptions, EscrowResourceSpecifier};
+
+/// Compiles the manifest text for the network and describes it, one line per instruction.
+pub fn explain_rtm(manifest: &str, network: &NetworkDefinition) -> Vec<String> {
+    let manifest = compile(manifest, network, BlobProvider::new())
+        .unwrap_or_else(|error| panic!("Invalid manifest for network {}: {:?}", network.logical_name, error));
+    explain(&manifest, network)
+}
+
+/// Describes the manifest, one line per instruction.
+pub fn explain(manifest: &TransactionManifestV1, network: &NetworkDefinition) -> Vec<String> {
+    let mut explainer = Explainer {
+        encoder: AddressBech32Encoder::new(network),
+        worktop: index_map_new(),
+        buckets: Vec::new(),
+    };
+    manifest.instructions
+        .iter()
+        .map(|instruction| explainer.explain(instruction))
+        .collect()
+}
+
+struct Explainer {
+    encoder: AddressBech32Encoder,
+    // What the account withdrawals put on the worktop, by resource
+    worktop: IndexMap<ResourceAddress, String>,
+    // What each bucket holds, by bucket ID
+    buckets: Vec<String>,
+}
+
+impl Explainer {
+    fn explain(&mut self, instruction: &InstructionV1) -> String {
+        match instruction {
+            InstructionV1::TakeFromWorktop { resource_address, amount } => {
+                self.worktop.swap_remove(resource_address);
+                self.new_bucket(format!("{} {}", amount, self.address(resource_address)))
+            }
+            InstructionV1::TakeNonFungiblesFromWorktop { resource_address, ids } => {
+                self.worktop.swap_remove(resource_address);
+                self.new_bucket(format!("{} {}", self.address(resource_address), Self::ids(ids)))
+            }
+            InstructionV1::TakeAllFromWorktop { resource_address } => {
+                let content = self.worktop
+                    .swap_remove(resource_address)
+                    .unwrap_or_else(|| format!("all the {} on the worktop", self.address(resource_address)));
+                self.new_bucket(content)
+            }
+            InstructionV1::AssertWorktopContains { resource_address, amount } => {
+                format!("Fails unless the worktop holds at least {} {}", amount, self.address(resource_address))
+            }
+            InstructionV1::AssertWorktopContainsNonFungibles { resource_address, ids } => {
+                format!("Fails unless the worktop holds {} {}", self.address(resource_address), Self::ids(ids))
+            }
+            InstructionV1::AssertWorktopContainsAny { resource_address } => {
+                format!("Fails unless the worktop holds some {}", self.address(resource_address))
+            }
+            InstructionV1::CallFunction { package_address, blueprint_name, function_name, args } => {
+                let instantiate_escrow_args = if blueprint_name == "Escrow" && function_name == "instantiate_escrow" {
+                    decode_args::<(Vec<EscrowResourceSpecifier>, ManifestBucket, EscrowOptions, Option<ManifestBucket>)>(args)
+                } else {
+                    None
+                };
+                if let Some((requested, offered, options, creation_deposit)) = instantiate_escrow_args {
+                    let package = match package_address {
+                        DynamicPackageAddress::Static(package_address) => self.address(package_address),
+                        DynamicPackageAddress::Named(_) => "a package published in this transaction".to_string(),
+                    };
+                    self.explain_instantiate_escrow(package, requested, offered, options, creation_deposit)
+                } else {
+                    format!("Calls the function {}::{}", blueprint_name, function_name)
+                }
+            }
+            InstructionV1::CallMethod { address, method_name, args } => {
+                let address = match address {
+                    DynamicGlobalAddress::Static(address) => address,
+                    DynamicGlobalAddress::Named(_) => {
+                        return format!("Calls {} on a component created in this transaction", method_name);
+                    }
+                };
+                self.explain_call_method(address, method_name, args)
+            }
+            other => format!("Runs {:?}", other),
+        }
+    }
+
+    fn explain_instantiate_escrow(
+        &self,
+        package: String,
+        requested: Vec<EscrowResourceSpecifier>,
+        offered: ManifestBucket,
+        options: EscrowOptions,
+        creation_deposit: Option<ManifestBucket>
+    ) -> String {
+        let requested: Vec<String> = requested.iter().map(|specifier| self.specifier(specifier)).collect();
+        let mut explanation = format!(
+            "Instantiates an escrow of package {} offering {} in exchange for any one of: {}",
+            package,
+            self.bucket(&offered),
+            requested.join(", ")
+        );
+
+        if let Some(settlement_account) = options.settlement_account {
+            explanation.push_str(&format!(", settling into {}", self.address(&settlement_account)));
+            if options.use_account_locker {
+                explanation.push_str(" through an account locker");
+            }
+        }
+        if let Some(callback) = options.callback {
+            explanation.push_str(&format!(", calling {} on {} when filled", callback.method, self.address(&callback.component)));
+        }
+        if options.mint_taker_receipt {
+            explanation.push_str(", minting taker receipts");
+        }
+        for (referrer, referral_fee) in options.referrers.iter().filter(|(_, fee)| **fee > Decimal::ZERO) {
+            explanation.push_str(&format!(
+                ", paying a referral fee of {} to {} {}",
+                referral_fee,
+                self.address(&referrer.resource_address()),
+                referrer.local_id()
+            ));
+        }
+        if let Some(creation_deposit) = creation_deposit {
+            explanation.push_str(&format!(", with a creation deposit of {}", self.bucket(&creation_deposit)));
+        }
+        explanation
+    }
+
+    fn explain_call_method(&mut self, address: &GlobalAddress, method_name: &str, args: &ManifestValue) -> String {
+        self.explain_known_method(address, method_name, args)
+            .unwrap_or_else(|| format!("Calls {} on {}", method_name, self.address(address)))
+    }
+
+    // Describes the account and `Escrow` methods, or `None` for other methods, including methods of
+    // the same name taking other arguments, like the `exchange` of the other escrow blueprints
+    fn explain_known_method(&mut self, address: &GlobalAddress, method_name: &str, args: &ManifestValue) -> Option<String> {
+        let component = self.address(address);
+        let explanation = match method_name {
+            "lock_fee" | "lock_contingent_fee" => {
+                let (amount,) = decode_args::<(Decimal,)>(args)?;
+                let payer = if *address == GlobalAddress::from(FAUCET) { "the faucet".to_string() } else { component };
+                let kind = if method_name == "lock_fee" { "fee" } else { "contingent fee, charged only on success," };
+                format!("Locks a {} of up to {} XRD from {}", kind, amount, payer)
+            }
+            "withdraw" => {
+                let (resource_address, amount) = decode_args::<(ResourceAddress, Decimal)>(args)?;
+                let content = format!("{} {}", amount, self.address(&resource_address));
+                self.worktop.insert(resource_address, content.clone());
+                format!("Withdraws {} from {}", content, component)
+            }
+            "withdraw_non_fungibles" => {
+                let (resource_address, ids) = decode_args::<(ResourceAddress, Vec<NonFungibleLocalId>)>(args)?;
+                let content = format!("{} {}", self.address(&resource_address), Self::ids(&ids));
+                self.worktop.insert(resource_address, content.clone());
+                format!("Withdraws {} from {}", content, component)
+            }
+            "deposit_batch" | "try_deposit_batch_or_abort" | "try_deposit_batch_or_refund" => {
+                format!("Deposits everything left on the worktop into {}", component)
+            }
+            "exchange" => {
+                let (paid, terms_revision, referrer) =
+                    decode_args::<(ManifestBucket, Option<u64>, Option<NonFungibleGlobalId>)>(args)?;
+                let mut explanation = format!("Pays {} to the escrow {} for its offered resource", self.bucket(&paid), component);
+                if let Some(terms_revision) = terms_revision {
+                    explanation.push_str(&format!(", failing unless its terms are at revision {}", terms_revision));
+                }
+                if let Some(referrer) = referrer {
+                    explanation.push_str(&format!(
+                        ", referred by {} {}",
+                        self.address(&referrer.resource_address()),
+                        referrer.local_id()
+                    ));
+                }
+                explanation
+            }
+            "withdraw_resource" => {
+                let (escrow_nft,) = decode_args::<(ManifestBucket,)>(args)?;
+                format!("Burns the escrow badge {} to withdraw the proceeds of the escrow {}", self.bucket(&escrow_nft), component)
+            }
+            "cancel_escrow" => {
+                let (escrow_nft,) = decode_args::<(ManifestBucket,)>(args)?;
+                format!("Burns the escrow badge {} to cancel the escrow {} and take back the offered resource", self.bucket(&escrow_nft), component)
+            }
+            _ => return None,
+        };
+        Some(explanation)
+    }
+
+    fn new_bucket(&mut self, content: String) -> String {
+        self.buckets.push(content.clone());
+        format!("Takes {} from the worktop into a bucket", content)
+    }
+
+    fn bucket(&self, bucket: &ManifestBucket) -> String {
+        self.buckets
+            .get(bucket.0 as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("bucket {}", bucket.0))
+    }
+
+    fn specifier(&self, specifier: &EscrowResourceSpecifier) -> String {
+        match specifier {
+            EscrowResourceSpecifier::Fungible { resource_address, amount } => {
+                format!("{} {}", amount, self.address(resource_address))
+            }
+            EscrowResourceSpecifier::NonFungible { resource_address, non_fungible_local_id } => {
+                format!("{} {}", self.address(resource_address), non_fungible_local_id)
+            }
+        }
+    }
+
+    fn address<A: Into<GlobalAddress> + Copy>(&self, address: &A) -> String {
+        let address: GlobalAddress = (*address).into();
+        self.encoder
+            .encode(address.as_node_id().as_bytes())
+            .expect("Failed to encode the address")
+    }
+
+    fn ids(ids: &[NonFungibleLocalId]) -> String {
+        ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ")
+    }
+}
+
+// The arguments as `T`, or `None` if they don't match it
+fn decode_args<T: ManifestDecode>(args: &ManifestValue) -> Option<T> {
+    let encoded = manifest_encode(args).expect("Failed to encode the arguments");
+    manifest_decode(&encoded).ok()
+}
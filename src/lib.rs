@@ -1,115 +1,232 @@
 use scrypto::prelude::*;
 
+mod bonded_escrow;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+#[cfg(all(feature = "dry-run", not(target_arch = "wasm32")))]
+pub mod dry_run;
+mod english_auction;
+mod escrow_governor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod explainer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod manifests;
+mod mock_escrow_callback;
+mod mock_price_oracle;
+mod oracle_escrow;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod profiles;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod resim;
+mod sealed_bid_auction;
+mod subscription_escrow;
+
+pub use bonded_escrow::BondStatus;
+pub use mock_escrow_callback::MOCK_ESCROW_CALLBACK_METHOD;
+pub use oracle_escrow::OraclePricing;
+pub use sealed_bid_auction::{sealed_bid_commitment, SealedBidReceipt};
+
 const DEFAULT_ESCROW_ID: u64 = 1;
+// Escrows cancelled before being open this long forfeit part of their creation deposit
+const MINIMUM_OPEN_SECONDS: i64 = 24 * 60 * 60;
+/// Package metadata naming the `EscrowGovernor` of every escrow and auction of the package, only
+/// the package owner can set it. None of them can be instantiated until it is set.
+pub const GOVERNOR_METADATA_KEY: &str = "governor";
 
 #[blueprint]
 mod escrow {
     struct Escrow {
-        requested_resource: EscrowResourceSpecifier,
+        // Alternatives the taker can choose from, each with its own vault at the same index
+        requested_resources: Vec<EscrowResourceSpecifier>,
         offered_resource: Vault,
-        requested_resource_vault: Vault,
+        requested_resource_vaults: Vec<Vault>,
         escrow_nft: ResourceAddress,
+        terms_revision: u64,
+        // Vaults replaced by `update_terms`, vaults can't be dropped so they are kept here
+        retired_vaults: Vec<Vault>,
+        settlement_account: Option<Global<Account>>,
+        // Holds what the instantiator is owed for them to claim from their settlement account
+        account_locker: Option<Global<AccountLocker>>,
+        callback: Option<EscrowCallback>,
+        taker_receipt: Option<ResourceAddress>,
+        // Share of fungible proceeds paid to each referrer badge, and the fees earned so far
+        referrers: IndexMap<NonFungibleGlobalId, Decimal>,
+        referral_fees: KeyValueStore<(NonFungibleGlobalId, ResourceAddress), Vault>,
+        // Optional XRD deposit against spam, partially forfeited to the governor by an early
+        // cancellation
+        creation_deposit: Vault,
+        created_at: Instant,
+        // The governor of the package when the escrow was instantiated
+        governor: ComponentAddress,
     }
 
     impl Escrow {
 
+        /// The optional XRD `creation_deposit` is returned by `withdraw_resource`, or partially
+        /// forfeited if the escrow is cancelled within `MINIMUM_OPEN_SECONDS` of its creation.
+        /// Fails while the governor of the package is paused.
         pub fn instantiate_escrow(
-            requested_resource: EscrowResourceSpecifier,
-            offered_resource: Bucket
+            requested_resources: Vec<EscrowResourceSpecifier>,
+            offered_resource: Bucket,
+            options: EscrowOptions,
+            creation_deposit: Option<Bucket>
         ) -> (Global<Escrow>, NonFungibleBucket) {
+            assert!(!requested_resources.is_empty(), "You must request at least one resource!");
+            let governor = package_governor();
+            assert_not_paused(governor);
+            let creation_deposit = creation_deposit.unwrap_or_else(|| Bucket::new(XRD));
+            assert_eq!(
+                creation_deposit.resource_address(),
+                XRD,
+                "The creation deposit must be paid in XRD!"
+            );
+            assert!(
+                !options.use_account_locker || options.settlement_account.is_some(),
+                "You must provide a settlement account to use an account locker!"
+            );
+            assert!(
+                options.referrers.values().all(|fee| *fee >= Decimal::ZERO && *fee < Decimal::ONE),
+                "The referral fee must be at least 0 and below 1!"
+            );
+
+            let (address_reservation, component_address) =
+                Runtime::allocate_component_address(Escrow::blueprint_id());
+
             // Create a new resource for the EscrowBadge NFT and mint it to the caller
-            let escrow_nft = ResourceBuilder::new_integer_non_fungible(OwnerRole::None)
-                .metadata(metadata!(
-                    init {
-                        "name" => "Cool radix hackathon escrow badge", locked;
-                    }
-                ))
-                .mint_roles(mint_roles!(
-                    minter => rule!(deny_all);
-                    minter_updater => rule!(deny_all);
-                ))
-                .burn_roles(burn_roles!(
-                    burner => rule!(allow_all); // TODO - allow only the escrow contract to burn?
-                    burner_updater => rule!(deny_all);
-                ))
-                .mint_initial_supply(vec![(
-                    IntegerNonFungibleLocalId::new(DEFAULT_ESCROW_ID),
-                    EscrowBadge {
-                        offered_resource: offered_resource.resource_address()
-                    }
-                )]);
+            let escrow_nft = create_escrow_badge(offered_resource.resource_address());
+
+            // Create a new vault for each requested resource
+            let requested_resource_vaults = requested_resources
+                .iter()
+                .map(|requested_resource| Vault::new(requested_resource.get_resource_address()))
+                .collect();
 
-            // Create a new vault for the requested resource
-            let requested_resource_vault = Vault::new(requested_resource.get_resource_address());
+            // Only this component can store in the locker, nobody can recover what it holds
+            let account_locker = options.use_account_locker.then(|| {
+                Blueprint::<AccountLocker>::instantiate(
+                    OwnerRole::None,
+                    rule!(require(global_caller(component_address))),
+                    rule!(deny_all),
+                    rule!(deny_all),
+                    rule!(deny_all),
+                    None,
+                )
+            });
+
+            // Taker receipts are transferable and can't be burned, so they keep proving provenance
+            let taker_receipt = options.mint_taker_receipt.then(|| {
+                ResourceBuilder::new_ruid_non_fungible::<TakerReceipt>(OwnerRole::None)
+                    .metadata(metadata!(
+                        init {
+                            "name" => "Escrow taker receipt", locked;
+                        }
+                    ))
+                    .mint_roles(mint_roles!(
+                        minter => rule!(require(global_caller(component_address)));
+                        minter_updater => rule!(deny_all);
+                    ))
+                    .create_with_no_initial_supply()
+                    .address()
+            });
 
             // Instantiate the Escrow component
             let escrow = Self {
-                requested_resource,
+                requested_resources,
                 offered_resource: Vault::with_bucket(offered_resource),
-                requested_resource_vault,
+                requested_resource_vaults,
                 escrow_nft: escrow_nft.resource_address(),
+                terms_revision: 0,
+                retired_vaults: Vec::new(),
+                settlement_account: options.settlement_account.map(Global::<Account>::from),
+                account_locker,
+                callback: options.callback,
+                taker_receipt,
+                referrers: options.referrers,
+                referral_fees: KeyValueStore::new(),
+                creation_deposit: Vault::with_bucket(creation_deposit),
+                created_at: Clock::current_time_rounded_to_seconds(),
+                governor,
             }
             .instantiate()
             .prepare_to_globalize(OwnerRole::None)
+            .with_address(address_reservation)
             .globalize();
 
             (escrow, escrow_nft)
         }
 
-        pub fn exchange(&mut self, bucket_of_resource: Bucket) -> Bucket {
-            // Assert that the offered resource has not been withdrawn
-            assert!(
-                !self.offered_resource.is_empty(),
-                "The offered resource has already been withdrawn!"
-            );
-            assert_eq!(
-                bucket_of_resource.resource_address(),
-                self.requested_resource_vault.resource_address(),
-                "You must exchange the requested resource, invalid resource specified!"
-            );
-            match &self.requested_resource {
-                EscrowResourceSpecifier::Fungible { amount, .. } => {
-                    assert_eq!(
-                        *amount,
-                        bucket_of_resource.amount(),
-                        "You must exchange the requested amount!"
-                    );
-                },
-                EscrowResourceSpecifier::NonFungible { non_fungible_local_id, .. } => {
-                    let bucket_id = bucket_of_resource.as_non_fungible().non_fungible_local_id();
-                    assert_eq!(
-                        *non_fungible_local_id,
-                        bucket_id,
-                        "You must exchange the requested non-fungible ID!"
-                    );
-                }
-            }
+        /// Accepts any one of the requested resources. Takers can pin the `terms_revision` they
+        /// agreed to, so the exchange fails if the terms are updated before it goes through.
+        /// Returns the offered resource, with a taker receipt if the escrow mints them. The
+        /// `referrer` badge, if any, earns its referral fee on fungible proceeds. Referrers missing
+        /// from the `referrers` of the options earn nothing, so takers can't refer themselves.
+        pub fn exchange(
+            &mut self,
+            bucket_of_resource: Bucket,
+            terms_revision: Option<u64>,
+            referrer: Option<NonFungibleGlobalId>
+        ) -> (Bucket, Option<NonFungibleBucket>) {
+            self.take_payment(bucket_of_resource, terms_revision, referrer)
+        }
 
-            self.requested_resource_vault.put(bucket_of_resource);
+        /// Exchanges like `exchange`, but stores the offered resource and the taker receipt in the
+        /// escrow's account locker for the `taker_account` to claim, so both parties settle
+        /// through it. Fails if the escrow has no account locker.
+        pub fn exchange_via_locker(
+            &mut self,
+            bucket_of_resource: Bucket,
+            terms_revision: Option<u64>,
+            referrer: Option<NonFungibleGlobalId>,
+            taker_account: ComponentAddress
+        ) {
+            assert!(self.account_locker.is_some(), "This escrow has no account locker!");
 
-            self.offered_resource.take_all()
+            let (offered, taker_receipt) = self.take_payment(bucket_of_resource, terms_revision, referrer);
+
+            let taker_account = Global::<Account>::from(taker_account);
+            let locker = self.account_locker.as_mut().unwrap();
+            locker.store(taker_account, offered, false);
+            if let Some(taker_receipt) = taker_receipt {
+                locker.store(taker_account, taker_receipt.into(), false);
+            }
         }
 
-        pub fn withdraw_resource(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+        /// Returns the proceeds collected in every requested resource vault, and the creation
+        /// deposit.
+        pub fn withdraw_resource(&mut self, escrow_nft: NonFungibleBucket) -> Vec<Bucket> {
             // Assert that the caller is authorized by checking the NFT
             assert_eq!(
                 escrow_nft.resource_address(),
                 self.escrow_nft,
                 "You must provide the correct escrow NFT to withdraw the resource"
             );
-            
+
+            let proceeds_held = self.requested_resource_vaults.iter().any(|vault| !vault.is_empty());
+            assert!(
+                proceeds_held || !self.offered_resource.is_empty() || !self.creation_deposit.is_empty(),
+                "The proceeds have already been sent to your settlement account"
+            );
+            // The offered resource is only emptied by an exchange, proceeds may have been pushed
             assert!(
-                !self.requested_resource_vault.is_empty(),
+                proceeds_held || self.offered_resource.is_empty(),
                 "The offer has not been accepted yet, you may want to cancel the escrow instead"
             );
 
             // Burn the escrow NFT to ensure it can't be used again
             escrow_nft.burn();
 
-            self.requested_resource_vault.take_all()
+            self.requested_resource_vaults
+                .iter_mut()
+                .chain([&mut self.creation_deposit])
+                .filter(|vault| !vault.is_empty())
+                .map(|vault| vault.take_all())
+                .collect()
         }
 
-        pub fn cancel_escrow(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+        /// Returns the offered resource, and the creation deposit if there is one. Cancelling
+        /// within `MINIMUM_OPEN_SECONDS` of the creation forfeits half of the deposit to the
+        /// governor, whose admin can withdraw it.
+        pub fn cancel_escrow(&mut self, escrow_nft: NonFungibleBucket) -> (Bucket, Option<Bucket>) {
             // Assert that the caller is authorized by checking the NFT
             assert_eq!(
                 escrow_nft.resource_address(),
@@ -122,23 +239,262 @@ mod escrow {
             );
             // Burn the escrow NFT to ensure it can't be used again
             escrow_nft.burn();
-            
-            self.offered_resource.take_all()
+
+            let deposit_refund = (!self.creation_deposit.is_empty()).then(|| {
+                let minimum_open_until = self.created_at.add_seconds(MINIMUM_OPEN_SECONDS).unwrap();
+                if Clock::current_time_is_strictly_before(minimum_open_until, TimePrecision::Second) {
+                    let forfeit = self.creation_deposit.amount() * dec!("0.5");
+                    let forfeit = self.creation_deposit.take(forfeit);
+                    Global::<AnyComponent>::from(self.governor)
+                        .call_raw::<()>("deposit_forfeited", scrypto_args!(forfeit));
+                }
+                self.creation_deposit.take_all()
+            });
+
+            // With an account locker the refund is stored for the instantiator to claim
+            let refund = self.offered_resource.take_all();
+            let refund = match (&mut self.account_locker, &self.settlement_account) {
+                (Some(locker), Some(account)) => {
+                    let resource_address = refund.resource_address();
+                    locker.store(*account, refund, false);
+                    Bucket::new(resource_address)
+                }
+                _ => refund,
+            };
+
+            (refund, deposit_refund)
+        }
+
+        /// Replaces the requested resources of an escrow which has not been taken yet, and returns
+        /// the new terms revision.
+        pub fn update_terms(&mut self, escrow_nft: Proof, requested_resources: Vec<EscrowResourceSpecifier>) -> u64 {
+            // Assert that the caller is authorized by checking the NFT
+            escrow_nft.check(self.escrow_nft);
+            assert!(
+                !self.offered_resource.is_empty(),
+                "The offered resource has already been withdrawn!"
+            );
+            assert!(!requested_resources.is_empty(), "You must request at least one resource!");
+
+            // Reuse the (empty) vaults of resources which are still requested, retire the others
+            let mut previous_vaults = std::mem::take(&mut self.requested_resource_vaults);
+            self.requested_resource_vaults = requested_resources
+                .iter()
+                .map(|requested_resource| {
+                    let resource_address = requested_resource.get_resource_address();
+                    match previous_vaults.iter().position(|vault| vault.resource_address() == resource_address) {
+                        Some(index) => previous_vaults.swap_remove(index),
+                        None => Vault::new(resource_address),
+                    }
+                })
+                .collect();
+            self.retired_vaults.extend(previous_vaults);
+
+            self.requested_resources = requested_resources;
+            self.terms_revision += 1;
+
+            self.terms_revision
+        }
+
+        /// Adds inventory to an open fungible offer. The requested amounts are scaled to keep the
+        /// same price per unit, and the terms revision is incremented. Fails if a non-fungible is
+        /// requested, as its price can't be scaled.
+        pub fn deposit_more_offered(&mut self, escrow_nft: Proof, bucket: Bucket) -> u64 {
+            // Assert that the caller is authorized by checking the NFT
+            escrow_nft.check(self.escrow_nft);
+            assert!(
+                !self.offered_resource.is_empty(),
+                "The offered resource has already been withdrawn!"
+            );
+            assert!(
+                self.offered_resource.resource_address().is_fungible(),
+                "Only fungible offers can be topped up!"
+            );
+
+            let previous_amount = self.offered_resource.amount();
+            self.offered_resource.put(bucket);
+            self.reprice(previous_amount)
+        }
+
+        /// Pulls part of the inventory out of an open fungible offer, leaving the escrow open. The
+        /// requested amounts are scaled to keep the same price per unit, and the terms revision is
+        /// incremented. Fails if a non-fungible is requested, as its price can't be scaled.
+        pub fn withdraw_offered_partial(&mut self, escrow_nft: Proof, amount: Decimal) -> (Bucket, u64) {
+            // Assert that the caller is authorized by checking the NFT
+            escrow_nft.check(self.escrow_nft);
+            assert!(
+                self.offered_resource.resource_address().is_fungible(),
+                "Only fungible offers can be partially withdrawn!"
+            );
+            assert!(
+                amount > Decimal::ZERO && amount < self.offered_resource.amount(),
+                "You can only withdraw part of the offered resource, cancel the escrow to withdraw all of it"
+            );
+
+            let previous_amount = self.offered_resource.amount();
+            let withdrawn = self.offered_resource.take(amount);
+            (withdrawn, self.reprice(previous_amount))
         }
 
         pub fn get_offered_resource_info(&self) -> EscrowResourceSpecifier {
-            let resource_address = self.offered_resource.resource_address();
-            if resource_address.is_fungible() {
-                EscrowResourceSpecifier::Fungible {
-                    resource_address,
-                    amount: self.offered_resource.amount()
+            EscrowResourceSpecifier::from_vault(&self.offered_resource)
+        }
+
+        pub fn get_requested_resource_info(&self) -> Vec<EscrowResourceSpecifier> {
+            self.requested_resources.clone()
+        }
+
+        pub fn get_terms_revision(&self) -> u64 {
+            self.terms_revision
+        }
+
+        /// Returns the referral fees earned in a resource by the badge of the proof.
+        pub fn claim_referral_fees(&mut self, referrer_badge: Proof, resource_address: ResourceAddress) -> Bucket {
+            // Any badge can be a referrer, the proof only needs to be genuine
+            let referrer_badge = referrer_badge.skip_checking().as_non_fungible();
+            let referrer = NonFungibleGlobalId::new(
+                referrer_badge.resource_address(),
+                referrer_badge.non_fungible_local_id()
+            );
+
+            let mut vault = self.referral_fees
+                .get_mut(&(referrer, resource_address))
+                .expect("This badge has not earned referral fees in this resource!");
+            vault.take_all()
+        }
+
+        pub fn get_referral_fees(&self, referrer: NonFungibleGlobalId, resource_address: ResourceAddress) -> Decimal {
+            self.referral_fees
+                .get(&(referrer, resource_address))
+                .map(|vault| vault.amount())
+                .unwrap_or(Decimal::ZERO)
+        }
+
+        pub fn get_creation_deposit(&self) -> Decimal {
+            self.creation_deposit.amount()
+        }
+
+        pub fn get_account_locker(&self) -> Option<ComponentAddress> {
+            self.account_locker.map(|locker| locker.address())
+        }
+
+        pub fn get_taker_receipt(&self) -> Option<ResourceAddress> {
+            self.taker_receipt
+        }
+
+        // Takes the payment for the offered resource, which is returned with the taker receipt
+        fn take_payment(
+            &mut self,
+            mut bucket_of_resource: Bucket,
+            terms_revision: Option<u64>,
+            referrer: Option<NonFungibleGlobalId>
+        ) -> (Bucket, Option<NonFungibleBucket>) {
+            assert_not_paused(self.governor);
+            // Assert that the offered resource has not been withdrawn
+            assert!(
+                !self.offered_resource.is_empty(),
+                "The offered resource has already been withdrawn!"
+            );
+            if let Some(terms_revision) = terms_revision {
+                assert_eq!(
+                    terms_revision,
+                    self.terms_revision,
+                    "The terms of the escrow have changed!"
+                );
+            }
+            let index = match self.requested_resources.as_slice() {
+                [requested_resource] => {
+                    requested_resource.assert_matches(&bucket_of_resource);
+                    0
                 }
-            } else {
-                EscrowResourceSpecifier::NonFungible {
-                    resource_address,
-                    non_fungible_local_id: self.offered_resource.as_non_fungible().non_fungible_local_id()
+                requested_resources => requested_resources
+                    .iter()
+                    .position(|requested_resource| requested_resource.matches(&bucket_of_resource))
+                    .expect("You must exchange one of the requested resources!"),
+            };
+
+            let referral = referrer.and_then(|referrer| {
+                let referral_fee = *self.referrers.get(&referrer)?;
+                Some((referrer, referral_fee))
+            });
+            if let Some((referrer, referral_fee)) = referral {
+                if referral_fee > Decimal::ZERO && bucket_of_resource.resource_address().is_fungible() {
+                    let fee_amount = bucket_of_resource.amount() * referral_fee;
+                    let fee = bucket_of_resource.take_advanced(
+                        fee_amount,
+                        WithdrawStrategy::Rounded(RoundingMode::ToZero)
+                    );
+                    self.deposit_referral_fee(referrer, fee);
                 }
             }
+
+            // Push the proceeds to the instantiator if they asked for it, keeping them for
+            // `withdraw_resource` if their account rejects the deposit
+            let bucket_of_resource = match (&mut self.account_locker, &mut self.settlement_account) {
+                (Some(locker), Some(account)) => {
+                    locker.store(*account, bucket_of_resource, false);
+                    None
+                }
+                (_, Some(account)) => account.try_deposit_or_refund(bucket_of_resource, None),
+                (_, None) => Some(bucket_of_resource),
+            };
+            if let Some(bucket_of_resource) = bucket_of_resource {
+                self.requested_resource_vaults[index].put(bucket_of_resource);
+            }
+
+            let mut taker_receipt = None;
+            if self.callback.is_some() || self.taker_receipt.is_some() {
+                let fill = EscrowFill {
+                    escrow: Runtime::global_address(),
+                    offered: EscrowResourceSpecifier::from_vault(&self.offered_resource),
+                    paid: self.requested_resources[index].clone(),
+                    terms_revision: self.terms_revision,
+                };
+                taker_receipt = self.taker_receipt.map(|taker_receipt| {
+                    ResourceManager::from(taker_receipt)
+                        .mint_ruid_non_fungible(TakerReceipt::new(&fill))
+                        .as_non_fungible()
+                });
+
+                // Notify the integrator's component once the escrow is filled
+                if let Some(callback) = &self.callback {
+                    Global::<AnyComponent>::from(callback.component)
+                        .call_raw::<()>(&callback.method, scrypto_args!(fill));
+                }
+            }
+
+            (self.offered_resource.take_all(), taker_receipt)
+        }
+
+        fn deposit_referral_fee(&mut self, referrer: NonFungibleGlobalId, fee: Bucket) {
+            let key = (referrer, fee.resource_address());
+            if let Some(mut vault) = self.referral_fees.get_mut(&key) {
+                vault.put(fee);
+                return;
+            }
+            self.referral_fees.insert(key, Vault::with_bucket(fee));
+        }
+
+        // Scales the requested amounts to the new offered amount, and bumps the revision
+        fn reprice(&mut self, previous_offered_amount: Decimal) -> u64 {
+            let offered_amount = self.offered_resource.amount();
+            for requested_resource in self.requested_resources.iter_mut() {
+                match requested_resource {
+                    EscrowResourceSpecifier::Fungible { resource_address, amount } => {
+                        *amount = round_up_to_divisibility(
+                            *amount * offered_amount / previous_offered_amount,
+                            *resource_address
+                        );
+                    }
+                    // A single NFT would otherwise buy a different amount for the same price
+                    EscrowResourceSpecifier::NonFungible { .. } => {
+                        panic!("The offered amount can't change while a non-fungible is requested, update the terms instead!");
+                    }
+                }
+            }
+            self.terms_revision += 1;
+
+            self.terms_revision
         }
     }
 }
@@ -171,6 +527,95 @@ impl EscrowResourceSpecifier {
             } => *resource_address,
         }
     }
+
+    // Whether a bucket holds exactly the specified resource
+    pub fn matches(&self, bucket: &Bucket) -> bool {
+        bucket.resource_address() == self.get_resource_address()
+            && match self {
+                Self::Fungible { amount, .. } => *amount == bucket.amount(),
+                Self::NonFungible { non_fungible_local_id, .. } => {
+                    bucket.amount() == Decimal::ONE
+                        && bucket.as_non_fungible().non_fungible_local_id() == *non_fungible_local_id
+                }
+            }
+    }
+
+    // Asserts that a bucket holds exactly the specified resource
+    pub fn assert_matches(&self, bucket: &Bucket) {
+        assert_eq!(
+            bucket.resource_address(),
+            self.get_resource_address(),
+            "You must exchange the requested resource, invalid resource specified!"
+        );
+        match self {
+            Self::Fungible { amount, .. } => {
+                assert_eq!(
+                    *amount,
+                    bucket.amount(),
+                    "You must exchange the requested amount!"
+                );
+            },
+            Self::NonFungible { non_fungible_local_id, .. } => {
+                let bucket_id = bucket.as_non_fungible().non_fungible_local_id();
+                assert_eq!(
+                    *non_fungible_local_id,
+                    bucket_id,
+                    "You must exchange the requested non-fungible ID!"
+                );
+            }
+        }
+    }
+
+    // Describes the content of a vault holding a fungible amount or a single non-fungible
+    pub fn from_vault(vault: &Vault) -> Self {
+        let resource_address = vault.resource_address();
+        if resource_address.is_fungible() {
+            Self::Fungible {
+                resource_address,
+                amount: vault.amount()
+            }
+        } else {
+            Self::NonFungible {
+                resource_address,
+                non_fungible_local_id: vault.as_non_fungible().non_fungible_local_id()
+            }
+        }
+    }
+}
+
+/// Opt-in behaviours of an `Escrow`, `EscrowOptions::default()` gives the plain escrow.
+#[derive(ScryptoSbor, ManifestSbor, Clone, Debug, Default)]
+pub struct EscrowOptions {
+    /// Account to which `exchange` deposits the proceeds directly. If the account rejects the
+    /// deposit, the proceeds are kept for `withdraw_resource`.
+    pub settlement_account: Option<ComponentAddress>,
+    /// Stores the proceeds and cancellation refunds in an `AccountLocker` instead, from which the
+    /// settlement account can claim them. Requires a `settlement_account`.
+    pub use_account_locker: bool,
+    /// Component method called by `exchange` with an `EscrowFill` once the escrow is filled
+    pub callback: Option<EscrowCallback>,
+    /// Mints a `TakerReceipt` NFT to the taker on `exchange`, recording the trade
+    pub mint_taker_receipt: bool,
+    /// Share of fungible proceeds paid to each referrer badge passed to `exchange`, `dec!("0.01")`
+    /// for 1%. Other referrers earn nothing.
+    pub referrers: IndexMap<NonFungibleGlobalId, Decimal>
+}
+
+/// A method of a third-party component, taking an `EscrowFill` and returning nothing, like
+/// `on_escrow_filled` of the `MockEscrowCallback` blueprint.
+#[derive(ScryptoSbor, ManifestSbor, Clone, Debug)]
+pub struct EscrowCallback {
+    pub component: ComponentAddress,
+    pub method: String
+}
+
+/// Summary of a filled escrow passed to its callback.
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct EscrowFill {
+    pub escrow: GlobalAddress,
+    pub offered: EscrowResourceSpecifier,
+    pub paid: EscrowResourceSpecifier,
+    pub terms_revision: u64
 }
 
 #[derive(ScryptoSbor, NonFungibleData)]
@@ -178,5 +623,87 @@ pub struct EscrowBadge {
     offered_resource: ResourceAddress
 }
 
+/// Proof of a trade made through an `Escrow`, minted to the taker.
+#[derive(ScryptoSbor, NonFungibleData, Clone, Debug)]
+pub struct TakerReceipt {
+    pub escrow: GlobalAddress,
+    pub offered: EscrowResourceSpecifier,
+    pub paid: EscrowResourceSpecifier,
+    pub traded_at: Instant,
+    /// What was paid per unit of the offered resource, when both sides are fungible
+    pub price: Option<Decimal>
+}
+
+impl TakerReceipt {
+
+    fn new(fill: &EscrowFill) -> Self {
+        let price = match (&fill.offered, &fill.paid) {
+            (
+                EscrowResourceSpecifier::Fungible { amount: offered_amount, .. },
+                EscrowResourceSpecifier::Fungible { amount: paid_amount, .. },
+            ) => Some(*paid_amount / *offered_amount),
+            _ => None,
+        };
+
+        Self {
+            escrow: fill.escrow,
+            offered: fill.offered.clone(),
+            paid: fill.paid.clone(),
+            traded_at: Clock::current_time_rounded_to_seconds(),
+            price
+        }
+    }
+}
+
+// Rounds an amount up to what a fungible resource can represent
+pub(crate) fn round_up_to_divisibility(amount: Decimal, resource_address: ResourceAddress) -> Decimal {
+    let divisibility = match ResourceManager::from(resource_address).resource_type() {
+        ResourceType::Fungible { divisibility } => divisibility,
+        ResourceType::NonFungible { .. } => panic!("The resource must be fungible!"),
+    };
+    amount
+        .checked_round(divisibility, RoundingMode::ToPositiveInfinity)
+        .unwrap()
+}
+
+// The `EscrowGovernor` named by the package owner in the package metadata
+pub(crate) fn package_governor() -> ComponentAddress {
+    let governor: Option<GlobalAddress> = Global::<Package>::from(Runtime::package_address())
+        .get_metadata(GOVERNOR_METADATA_KEY)
+        .expect("The governor in the package metadata must be an address!");
+    let governor = governor.expect("The package owner must set the governor of its escrows first!");
+    ComponentAddress::try_from(governor.as_node_id().0).expect("The governor of the package must be a component!")
+}
+
+// Panics if the `EscrowGovernor` at this address has paused its escrows
+pub(crate) fn assert_not_paused(governor: ComponentAddress) {
+    let paused: bool = Global::<AnyComponent>::from(governor).call_raw("is_paused", scrypto_args!());
+    assert!(!paused, "Escrows have been paused by their governor!");
+}
+
+// Creates the EscrowBadge resource and mints the single badge handed to the instantiator
+pub(crate) fn create_escrow_badge(offered_resource: ResourceAddress) -> NonFungibleBucket {
+    ResourceBuilder::new_integer_non_fungible(OwnerRole::None)
+        .metadata(metadata!(
+            init {
+                "name" => "Cool radix hackathon escrow badge", locked;
+            }
+        ))
+        .mint_roles(mint_roles!(
+            minter => rule!(deny_all);
+            minter_updater => rule!(deny_all);
+        ))
+        .burn_roles(burn_roles!(
+            burner => rule!(allow_all); // TODO - allow only the escrow contract to burn?
+            burner_updater => rule!(deny_all);
+        ))
+        .mint_initial_supply(vec![(
+            IntegerNonFungibleLocalId::new(DEFAULT_ESCROW_ID),
+            EscrowBadge {
+                offered_resource
+            }
+        )])
+}
+
 
 #[cfg(test)] mod tests;
\ No newline at end of file
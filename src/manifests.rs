@@ -0,0 +1,337 @@
+//! Manifest fragments for the operations of the `Escrow` blueprint, shared by the `escrow`
+//! binary and the tests. Each function appends its instructions to a `ManifestBuilder`, so the
+//! caller decides how the fee is locked, usually by starting with `lock_fee`. The `guarantee` of
+//! an operation, when known, is asserted on the worktop before depositing what the escrow
+//! returned.
+
+use radix_transactions::manifest::{decompiler::decompile_with_known_naming, dumper::dump_manifest_to_file_system};
+use radix_transactions::prelude::ManifestBuilder;
+use scrypto::prelude::*;
+use std::path::Path;
+
+use crate::{EscrowOptions, EscrowResourceSpecifier, GOVERNOR_METADATA_KEY};
+
+/// How a manifest pays its transaction fee, the `--fee` of the `escrow` binary and the `fee` of
+/// its network profiles.
+#[derive(serde::Deserialize, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeStrategy {
+    /// Leaves the fee to the wallet signing the transaction
+    None,
+    /// Locks the fee from the faucet, only available on test networks
+    Faucet,
+    /// Locks the fee from an account
+    Account,
+    /// Locks the fee from an account, plus as much again as a contingent fee which is only charged
+    /// if the transaction succeeds. The network rejects transactions locking only contingent fees
+    Contingent,
+}
+
+/// Starts a manifest by locking the fee according to the strategy, up to `amount` XRD from the
+/// `account` for the strategies paying from an account.
+pub fn lock_fee(builder: ManifestBuilder, strategy: FeeStrategy, account: ComponentAddress, amount: Decimal) -> ManifestBuilder {
+    match strategy {
+        FeeStrategy::None => builder,
+        FeeStrategy::Faucet => builder.lock_fee_from_faucet(),
+        FeeStrategy::Account => builder.lock_fee(account, amount),
+        FeeStrategy::Contingent => builder
+            .lock_fee(account, amount)
+            .call_method(account, "lock_contingent_fee", manifest_args!(amount)),
+    }
+}
+
+/// The files a manifest is written to by `write_manifest`, the `--format` of the `escrow` binary.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ManifestFormat {
+    /// The `<name>.rtm` text, with its blobs in `<hash>.blob` files
+    Rtm,
+    /// The SBOR-encoded compiled manifest in `<name>.bin`
+    Compiled,
+    /// The request of the Radix dApp Toolkit `sendTransaction` call in `<name>.json`
+    Json,
+}
+
+/// Builds the manifest and writes it to the directory in each of the formats.
+pub fn write_manifest(
+    builder: ManifestBuilder,
+    directory: &Path,
+    name: &str,
+    network: &NetworkDefinition,
+    formats: &[ManifestFormat]
+) {
+    let object_names = builder.object_names();
+    let manifest = builder.build();
+    std::fs::create_dir_all(directory).expect("Failed to create the manifest directory");
+
+    for format in formats {
+        match format {
+            ManifestFormat::Rtm => {
+                dump_manifest_to_file_system(object_names.clone(), &manifest, directory, Some(name), network)
+                    .expect("Failed to write the manifest");
+            }
+            ManifestFormat::Compiled => {
+                let compiled = manifest_encode(&manifest).expect("Failed to encode the manifest");
+                std::fs::write(directory.join(format!("{}.bin", name)), compiled)
+                    .expect("Failed to write the compiled manifest");
+            }
+            ManifestFormat::Json => {
+                let transaction_manifest = decompile_with_known_naming(&manifest.instructions, network, object_names.clone())
+                    .expect("Failed to decompile the manifest");
+                let blobs: Vec<String> = manifest.blobs
+                    .values()
+                    .map(|blob| blob.iter().map(|byte| format!("{:02x}", byte)).collect())
+                    .collect();
+                let request = serde_json::json!({
+                    "transactionManifest": transaction_manifest,
+                    "blobs": blobs,
+                    "version": 1,
+                });
+                std::fs::write(
+                    directory.join(format!("{}.json", name)),
+                    serde_json::to_string_pretty(&request).expect("Failed to encode the JSON manifest")
+                ).expect("Failed to write the JSON manifest");
+            }
+        }
+    }
+}
+
+/// Withdraws the specified resource from the account into a named bucket.
+pub fn withdraw_specified(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    specifier: &EscrowResourceSpecifier,
+    bucket: &str
+) -> ManifestBuilder {
+    let builder = match specifier {
+        EscrowResourceSpecifier::Fungible { resource_address, amount } => {
+            builder.withdraw_from_account(account, *resource_address, *amount)
+        }
+        EscrowResourceSpecifier::NonFungible { resource_address, non_fungible_local_id } => {
+            builder.withdraw_non_fungibles_from_account(account, *resource_address, [non_fungible_local_id.clone()])
+        }
+    };
+    builder.take_all_from_worktop(specifier.get_resource_address(), bucket)
+}
+
+/// Asserts the worktop holds at least the specified resource.
+pub fn assert_worktop_contains_specified(
+    builder: ManifestBuilder,
+    specifier: &EscrowResourceSpecifier
+) -> ManifestBuilder {
+    match specifier {
+        EscrowResourceSpecifier::Fungible { resource_address, amount } => {
+            builder.assert_worktop_contains(*resource_address, *amount)
+        }
+        EscrowResourceSpecifier::NonFungible { resource_address, non_fungible_local_id } => {
+            builder.assert_worktop_contains_non_fungibles(*resource_address, [non_fungible_local_id.clone()])
+        }
+    }
+}
+
+/// The guarantee of `withdraw_resource` on an escrow requesting a single resource, less the
+/// highest fee of its `referrers` for fungible proceeds. Escrows listing alternatives have none,
+/// as the alternative the taker pays with is not known in advance.
+pub fn withdraw_guarantee(
+    requested: &[EscrowResourceSpecifier],
+    referral_fee: Decimal
+) -> Option<EscrowResourceSpecifier> {
+    match requested {
+        [EscrowResourceSpecifier::Fungible { resource_address, amount }] => Some(EscrowResourceSpecifier::Fungible {
+            resource_address: *resource_address,
+            amount: *amount * (Decimal::ONE - referral_fee),
+        }),
+        [non_fungible] => Some(non_fungible.clone()),
+        _ => None,
+    }
+}
+
+fn deposit_guaranteed(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    let builder = match guarantee {
+        Some(guarantee) => assert_worktop_contains_specified(builder, guarantee),
+        None => builder,
+    };
+    builder.deposit_batch(account)
+}
+
+/// Puts the offered resource in a new escrow, the account receives the escrow badge.
+pub fn instantiate_escrow(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    package_address: PackageAddress,
+    offered: &EscrowResourceSpecifier,
+    requested: Vec<EscrowResourceSpecifier>,
+    options: EscrowOptions
+) -> ManifestBuilder {
+    instantiate_escrow_with_deposit(builder, account, package_address, offered, requested, options, None)
+}
+
+/// Like `instantiate_escrow`, also withdrawing the XRD `creation_deposit` from the account.
+pub fn instantiate_escrow_with_deposit(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    package_address: PackageAddress,
+    offered: &EscrowResourceSpecifier,
+    requested: Vec<EscrowResourceSpecifier>,
+    options: EscrowOptions,
+    creation_deposit: Option<Decimal>
+) -> ManifestBuilder {
+    let builder = withdraw_specified(builder, account, offered, "offered");
+    let builder = match creation_deposit {
+        Some(amount) => builder
+            .withdraw_from_account(account, XRD, amount)
+            .take_from_worktop(XRD, amount, "creation_deposit"),
+        None => builder,
+    };
+    builder
+        .call_function_with_name_lookup(
+            package_address,
+            "Escrow",
+            "instantiate_escrow",
+            |lookup| (
+                requested,
+                lookup.bucket("offered"),
+                options,
+                creation_deposit.map(|_| lookup.bucket("creation_deposit")),
+            ),
+        )
+        .deposit_batch(account)
+}
+
+/// Creates an `EscrowGovernor`, the account receives its admin badge.
+pub fn instantiate_escrow_governor(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    package_address: PackageAddress
+) -> ManifestBuilder {
+    builder
+        .call_function(package_address, "EscrowGovernor", "instantiate_escrow_governor", manifest_args!())
+        .deposit_batch(account)
+}
+
+/// Names the governor of every escrow of the package, the transaction must be authorized by the
+/// package owner.
+pub fn set_package_governor(
+    builder: ManifestBuilder,
+    package_address: PackageAddress,
+    governor: ComponentAddress
+) -> ManifestBuilder {
+    builder.set_metadata(
+        GlobalAddress::from(package_address),
+        GOVERNOR_METADATA_KEY,
+        MetadataValue::GlobalAddress(governor.into())
+    )
+}
+
+/// Pays one of the requested resources, the account receives the offered resource, which can
+/// be guaranteed.
+pub fn exchange(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    paid: &EscrowResourceSpecifier,
+    terms_revision: Option<u64>,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    exchange_with_referrer(builder, account, component_address, paid, terms_revision, None, guarantee)
+}
+
+/// Like `exchange`, naming the referrer badge earning its share of the proceeds.
+pub fn exchange_with_referrer(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    paid: &EscrowResourceSpecifier,
+    terms_revision: Option<u64>,
+    referrer: Option<NonFungibleGlobalId>,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    let builder = withdraw_specified(builder, account, paid, "paid")
+        .call_method_with_name_lookup(
+            component_address,
+            "exchange",
+            |lookup| (
+                lookup.bucket("paid"),
+                terms_revision,
+                referrer,
+            ),
+        );
+    deposit_guaranteed(builder, account, guarantee)
+}
+
+pub fn exchange_fungible(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    resource_address: ResourceAddress,
+    amount: Decimal,
+    terms_revision: Option<u64>,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    let paid = EscrowResourceSpecifier::Fungible { resource_address, amount };
+    exchange(builder, account, component_address, &paid, terms_revision, guarantee)
+}
+
+pub fn exchange_non_fungible(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    resource_address: ResourceAddress,
+    non_fungible_local_id: NonFungibleLocalId,
+    terms_revision: Option<u64>,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    let paid = EscrowResourceSpecifier::NonFungible { resource_address, non_fungible_local_id };
+    exchange(builder, account, component_address, &paid, terms_revision, guarantee)
+}
+
+/// Burns the escrow badge held by the account to withdraw the proceeds, which can be
+/// guaranteed.
+pub fn withdraw_resource(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    escrow_nft: ResourceAddress,
+    escrow_nft_id: NonFungibleLocalId,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    let builder = call_with_escrow_nft(builder, account, component_address, escrow_nft, escrow_nft_id, "withdraw_resource");
+    deposit_guaranteed(builder, account, guarantee)
+}
+
+/// Burns the escrow badge held by the account to take back the offered resource, which can be
+/// guaranteed.
+pub fn cancel_escrow(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    escrow_nft: ResourceAddress,
+    escrow_nft_id: NonFungibleLocalId,
+    guarantee: Option<&EscrowResourceSpecifier>
+) -> ManifestBuilder {
+    let builder = call_with_escrow_nft(builder, account, component_address, escrow_nft, escrow_nft_id, "cancel_escrow");
+    deposit_guaranteed(builder, account, guarantee)
+}
+
+fn call_with_escrow_nft(
+    builder: ManifestBuilder,
+    account: ComponentAddress,
+    component_address: ComponentAddress,
+    escrow_nft: ResourceAddress,
+    escrow_nft_id: NonFungibleLocalId,
+    method: &str
+) -> ManifestBuilder {
+    builder
+        .withdraw_non_fungibles_from_account(account, escrow_nft, [escrow_nft_id])
+        .take_all_from_worktop(escrow_nft, "escrow_nft")
+        .call_method_with_name_lookup(
+            component_address,
+            method,
+            |lookup| (
+                lookup.bucket("escrow_nft"),
+            ),
+        )
+}
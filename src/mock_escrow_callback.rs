@@ -0,0 +1,35 @@
+use scrypto::prelude::*;
+
+use crate::EscrowFill;
+
+/// The method of `MockEscrowCallback` to register in an `EscrowCallback`.
+pub const MOCK_ESCROW_CALLBACK_METHOD: &str = "on_escrow_filled";
+
+/// An escrow callback for tests and demos: it records every fill it is notified of. Integrators
+/// can expose the same `on_escrow_filled` method to mint receipts or reward takers.
+#[blueprint]
+mod mock_escrow_callback {
+    struct MockEscrowCallback {
+        fills: Vec<EscrowFill>,
+    }
+
+    impl MockEscrowCallback {
+
+        pub fn instantiate_mock_escrow_callback() -> Global<MockEscrowCallback> {
+            Self {
+                fills: Vec::new(),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize()
+        }
+
+        pub fn on_escrow_filled(&mut self, fill: EscrowFill) {
+            self.fills.push(fill);
+        }
+
+        pub fn get_fills(&self) -> Vec<EscrowFill> {
+            self.fills.clone()
+        }
+    }
+}
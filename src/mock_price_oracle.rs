@@ -0,0 +1,35 @@
+use scrypto::prelude::*;
+
+/// A price oracle for tests and demos: anyone can set a price, which is timestamped with the
+/// current ledger time. Any component exposing the same `get_price` method can be used by the
+/// `OracleEscrow` blueprint.
+#[blueprint]
+mod mock_price_oracle {
+    struct MockPriceOracle {
+        prices: KeyValueStore<(ResourceAddress, ResourceAddress), (Decimal, Instant)>,
+    }
+
+    impl MockPriceOracle {
+
+        pub fn instantiate_mock_price_oracle() -> Global<MockPriceOracle> {
+            Self {
+                prices: KeyValueStore::new(),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize()
+        }
+
+        pub fn set_price(&mut self, base: ResourceAddress, quote: ResourceAddress, price: Decimal) {
+            assert!(price > Decimal::ZERO, "The price must be positive!");
+            self.prices.insert((base, quote), (price, Clock::current_time_rounded_to_seconds()));
+        }
+
+        /// Returns the price of one unit of `base` in `quote`, and when it was last updated.
+        pub fn get_price(&self, base: ResourceAddress, quote: ResourceAddress) -> (Decimal, Instant) {
+            *self.prices
+                .get(&(base, quote))
+                .expect("No price is available for this pair!")
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use scrypto::prelude::*;
+
+use crate::{assert_not_paused, create_escrow_badge, package_governor, round_up_to_divisibility, EscrowResourceSpecifier};
+
+#[blueprint]
+mod oracle_escrow {
+    struct OracleEscrow {
+        offered_resource: Vault,
+        requested_resource_vault: Vault,
+        pricing: OraclePricing,
+        escrow_nft: ResourceAddress,
+        // The governor of the package when the escrow was instantiated
+        governor: ComponentAddress,
+    }
+
+    impl OracleEscrow {
+
+        /// Fails while the governor of the package is paused.
+        pub fn instantiate_oracle_escrow(
+            offered_resource: Bucket,
+            payment_resource: ResourceAddress,
+            pricing: OraclePricing
+        ) -> (Global<OracleEscrow>, NonFungibleBucket) {
+            let governor = package_governor();
+            assert_not_paused(governor);
+            assert!(!offered_resource.is_empty(), "You must offer a resource!");
+            assert!(payment_resource.is_fungible(), "The payment resource must be fungible!");
+            assert!(pricing.reference_price > Decimal::ZERO, "The reference price must be positive!");
+            assert!(pricing.max_slippage >= Decimal::ZERO, "The maximum slippage cannot be negative!");
+            assert!(pricing.price_adjustment > dec!(-1), "The price adjustment must be above -100%!");
+            assert!(pricing.max_price_age_seconds >= 0, "The maximum price age cannot be negative!");
+
+            let escrow_nft = create_escrow_badge(offered_resource.resource_address());
+
+            let escrow = Self {
+                offered_resource: Vault::with_bucket(offered_resource),
+                requested_resource_vault: Vault::new(payment_resource),
+                pricing,
+                escrow_nft: escrow_nft.resource_address(),
+                governor,
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize();
+
+            (escrow, escrow_nft)
+        }
+
+        /// Takes the price of the offered resource out of the payment and returns the offered
+        /// resource together with the change.
+        pub fn exchange(&mut self, mut payment: Bucket) -> (Bucket, Bucket) {
+            assert_not_paused(self.governor);
+            // Assert that the offered resource has not been withdrawn
+            assert!(
+                !self.offered_resource.is_empty(),
+                "The offered resource has already been withdrawn!"
+            );
+            assert_eq!(
+                payment.resource_address(),
+                self.requested_resource_vault.resource_address(),
+                "You must exchange the requested resource, invalid resource specified!"
+            );
+
+            let required = self.get_requested_resource_info();
+            let EscrowResourceSpecifier::Fungible { amount, .. } = required else {
+                unreachable!()
+            };
+            assert!(
+                payment.amount() >= amount,
+                "You must exchange at least {} at the current price!",
+                amount
+            );
+
+            self.requested_resource_vault.put(payment.take(amount));
+
+            (self.offered_resource.take_all(), payment)
+        }
+
+        pub fn withdraw_resource(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to withdraw the resource"
+            );
+
+            assert!(
+                !self.requested_resource_vault.is_empty(),
+                "The offer has not been accepted yet, you may want to cancel the escrow instead"
+            );
+
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            self.requested_resource_vault.take_all()
+        }
+
+        pub fn cancel_escrow(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to withdraw the resource"
+            );
+            assert!(
+                !self.offered_resource.is_empty(),
+                "The offered resource has already been withdrawn!"
+            );
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            self.offered_resource.take_all()
+        }
+
+        pub fn get_offered_resource_info(&self) -> EscrowResourceSpecifier {
+            EscrowResourceSpecifier::from_vault(&self.offered_resource)
+        }
+
+        /// Returns what `exchange` currently requires for the offered resource, after checking
+        /// the oracle price is fresh and within the slippage bound.
+        pub fn get_requested_resource_info(&self) -> EscrowResourceSpecifier {
+            let payment_resource = self.requested_resource_vault.resource_address();
+            let oracle: Global<AnyComponent> = Global::from(self.pricing.oracle);
+            let (price, updated_at): (Decimal, Instant) = oracle.call_raw(
+                "get_price",
+                scrypto_args!(self.offered_resource.resource_address(), payment_resource),
+            );
+
+            let now = Clock::current_time_rounded_to_seconds();
+            assert!(
+                now.seconds_since_unix_epoch - updated_at.seconds_since_unix_epoch
+                    <= self.pricing.max_price_age_seconds,
+                "The oracle price is stale!"
+            );
+            let slippage = (price - self.pricing.reference_price).checked_abs().unwrap()
+                / self.pricing.reference_price;
+            assert!(
+                slippage <= self.pricing.max_slippage,
+                "The oracle price moved more than the allowed slippage!"
+            );
+
+            let amount = round_up_to_divisibility(
+                self.offered_resource.amount() * price * (Decimal::ONE + self.pricing.price_adjustment),
+                payment_resource
+            );
+
+            EscrowResourceSpecifier::Fungible {
+                resource_address: payment_resource,
+                amount
+            }
+        }
+    }
+}
+
+/// How an `OracleEscrow` prices its offered resource. The oracle must expose
+/// `get_price(base: ResourceAddress, quote: ResourceAddress) -> (Decimal, Instant)`, like the
+/// `MockPriceOracle` blueprint.
+#[derive(ScryptoSbor, ManifestSbor, Clone, Debug)]
+pub struct OraclePricing {
+    pub oracle: ComponentAddress,
+    /// Applied on top of the oracle price, `dec!("-0.02")` sells at the oracle price minus 2%
+    pub price_adjustment: Decimal,
+    /// The price the instantiator expects, the oracle price must stay within `max_slippage` of it
+    pub reference_price: Decimal,
+    /// The allowed relative deviation from the reference price, `dec!("0.05")` for 5%
+    pub max_slippage: Decimal,
+    /// How old the oracle price may be, in seconds
+    pub max_price_age_seconds: i64
+}
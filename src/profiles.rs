@@ -0,0 +1,168 @@
+//! The network profiles of `network_profiles.toml`, giving the network of the manifests `escrow`
+//! writes and the addresses, fee and escrows they default to.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use scrypto::prelude::*;
+
+use crate::manifests::{self, FeeStrategy};
+use crate::EscrowResourceSpecifier;
+
+#[derive(Deserialize)]
+struct ProfileConfig {
+    network: String,
+    package: Option<String>,
+    account: Option<String>,
+    component: Option<String>,
+    badge: Option<String>,
+    fee: Option<FeeStrategy>,
+    fee_amount: Option<String>,
+    #[serde(default)]
+    escrows: Vec<EscrowConfig>,
+}
+
+#[derive(Deserialize)]
+struct EscrowConfig {
+    component: String,
+    offered: String,
+    requested: Vec<String>,
+    referral_fee: Option<String>,
+}
+
+/// A network profile of `network_profiles.toml`, with its addresses validated against the
+/// network.
+pub struct NetworkProfile {
+    pub network: NetworkDefinition,
+    pub package: Option<PackageAddress>,
+    pub account: Option<ComponentAddress>,
+    pub component: Option<ComponentAddress>,
+    pub badge: Option<ResourceAddress>,
+    /// Faucet on test networks and account on mainnet, unless the profile says otherwise
+    pub fee: FeeStrategy,
+    pub fee_amount: Decimal,
+    pub escrows: Vec<KnownEscrow>,
+}
+
+/// An escrow described by a profile, whose resources give the default guarantees of the
+/// manifests calling it.
+pub struct KnownEscrow {
+    pub component: ComponentAddress,
+    pub offered: EscrowResourceSpecifier,
+    pub requested: Vec<EscrowResourceSpecifier>,
+    /// The highest fee of the escrow's `referrers`
+    pub referral_fee: Decimal,
+}
+
+impl KnownEscrow {
+
+    /// What `withdraw_resource` returns, see `manifests::withdraw_guarantee`.
+    pub fn withdraw_guarantee(&self) -> Option<EscrowResourceSpecifier> {
+        manifests::withdraw_guarantee(&self.requested, self.referral_fee)
+    }
+}
+
+impl NetworkProfile {
+
+    pub fn load(path: &str, name: &str) -> Self {
+        let config = std::fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Failed to read the network profiles {}: {}", path, error));
+        let mut profiles: BTreeMap<String, ProfileConfig> = toml::from_str(&config)
+            .unwrap_or_else(|error| panic!("Invalid network profiles {}: {}", path, error));
+        let profile = profiles
+            .remove(name)
+            .unwrap_or_else(|| panic!("There is no {} profile in {}", name, path));
+
+        let network = match profile.network.as_str() {
+            "simulator" => NetworkDefinition::simulator(),
+            "localnet" => NetworkDefinition::localnet(),
+            "stokenet" => NetworkDefinition::stokenet(),
+            "mainnet" => NetworkDefinition::mainnet(),
+            network => panic!("Unknown network {} in the {} profile", network, name),
+        };
+        let decoder = AddressBech32Decoder::new(&network);
+        let default_fee = if network.id == NetworkDefinition::mainnet().id {
+            FeeStrategy::Account
+        } else {
+            FeeStrategy::Faucet
+        };
+
+        Self {
+            package: profile.package.map(|address| parse_package_address(&decoder, &address)),
+            account: profile.account.map(|address| parse_component_address(&decoder, &address)),
+            component: profile.component.map(|address| parse_component_address(&decoder, &address)),
+            badge: profile.badge.map(|address| parse_resource_address(&decoder, &address)),
+            fee: profile.fee.unwrap_or(default_fee),
+            fee_amount: profile.fee_amount
+                .map(|amount| Decimal::from_str(&amount).expect("Invalid fee amount"))
+                .unwrap_or(dec!("10")),
+            escrows: profile.escrows
+                .iter()
+                .map(|escrow| KnownEscrow {
+                    component: parse_component_address(&decoder, &escrow.component),
+                    offered: parse_resource_specifier(&decoder, &escrow.offered),
+                    requested: escrow.requested
+                        .iter()
+                        .map(|requested| parse_resource_specifier(&decoder, requested))
+                        .collect(),
+                    referral_fee: escrow.referral_fee
+                        .as_ref()
+                        .map(|fee| Decimal::from_str(fee).expect("Invalid referral fee"))
+                        .unwrap_or(Decimal::ZERO),
+                })
+                .collect(),
+            network,
+        }
+    }
+
+    /// The escrow of the profile at this address, if it describes it.
+    pub fn escrow(&self, component: ComponentAddress) -> Option<&KnownEscrow> {
+        self.escrows.iter().find(|escrow| escrow.component == component)
+    }
+}
+
+pub fn parse_package_address(decoder: &AddressBech32Decoder, address: &str) -> PackageAddress {
+    PackageAddress::try_from_bech32(decoder, address)
+        .unwrap_or_else(|| panic!("Invalid package address {} for this network", address))
+}
+
+pub fn parse_component_address(decoder: &AddressBech32Decoder, address: &str) -> ComponentAddress {
+    ComponentAddress::try_from_bech32(decoder, address)
+        .unwrap_or_else(|| panic!("Invalid component address {} for this network", address))
+}
+
+pub fn parse_resource_address(decoder: &AddressBech32Decoder, address: &str) -> ResourceAddress {
+    ResourceAddress::try_from_bech32(decoder, address)
+        .unwrap_or_else(|| panic!("Invalid resource address {} for this network", address))
+}
+
+// Parses `<resource_address>:<non_fungible_local_id>`
+pub fn parse_non_fungible_global_id(decoder: &AddressBech32Decoder, global_id: &str) -> NonFungibleGlobalId {
+    let (address, local_id) = global_id
+        .split_once(':')
+        .unwrap_or_else(|| panic!("Invalid badge {}, expected <resource_address>:<local_id>", global_id));
+
+    NonFungibleGlobalId::new(
+        parse_resource_address(decoder, address),
+        NonFungibleLocalId::from_str(local_id).expect("Invalid non-fungible ID")
+    )
+}
+
+// Parses `<resource_address>:<amount>` or `<resource_address>:<non_fungible_local_id>`
+pub fn parse_resource_specifier(decoder: &AddressBech32Decoder, specifier: &str) -> EscrowResourceSpecifier {
+    let (address, amount_or_id) = specifier
+        .split_once(':')
+        .unwrap_or_else(|| panic!("Invalid resource {}, expected <resource_address>:<amount or ID>", specifier));
+    let resource_address = parse_resource_address(decoder, address);
+
+    if resource_address.is_fungible() {
+        EscrowResourceSpecifier::Fungible {
+            resource_address,
+            amount: Decimal::from_str(amount_or_id).expect("Invalid amount")
+        }
+    } else {
+        EscrowResourceSpecifier::NonFungible {
+            resource_address,
+            non_fungible_local_id: NonFungibleLocalId::from_str(amount_or_id).expect("Invalid non-fungible ID")
+        }
+    }
+}
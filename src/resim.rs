@@ -0,0 +1,155 @@
+//! The resim shell script of an end-to-end escrow demo, written by `escrow resim-script`. The
+//! manifests it runs are built by the `manifests` module with placeholder addresses, which the
+//! script replaces with the addresses resim gives the accounts, package, governor, tokens and
+//! escrow.
+
+use radix_transactions::manifest::decompiler::decompile_with_known_naming;
+use radix_transactions::prelude::ManifestBuilder;
+use scrypto::prelude::*;
+
+use crate::{manifests, EscrowOptions, EscrowResourceSpecifier, DEFAULT_ESCROW_ID};
+
+/// The script of a demo where the first account offers `offered_amount` of a token for
+/// `requested_amount` of a token held by the second account, which takes the offer, before the
+/// first account withdraws the proceeds.
+pub fn demo_script(offered_amount: Decimal, requested_amount: Decimal) -> String {
+    let network = NetworkDefinition::simulator();
+    let encoder = AddressBech32Encoder::new(&network);
+
+    // The shell variables holding each address, by the placeholder the manifests are built with
+    let account1 = ComponentAddress::new_or_panic(placeholder(EntityType::GlobalAccount, 1));
+    let account2 = ComponentAddress::new_or_panic(placeholder(EntityType::GlobalAccount, 2));
+    let package = PackageAddress::new_or_panic(placeholder(EntityType::GlobalPackage, 3));
+    let offered = ResourceAddress::new_or_panic(placeholder(EntityType::GlobalFungibleResourceManager, 4));
+    let requested = ResourceAddress::new_or_panic(placeholder(EntityType::GlobalFungibleResourceManager, 5));
+    let escrow = ComponentAddress::new_or_panic(placeholder(EntityType::GlobalGenericComponent, 6));
+    let escrow_badge = ResourceAddress::new_or_panic(placeholder(EntityType::GlobalNonFungibleResourceManager, 7));
+    let owner_badge = ResourceAddress::new_or_panic(placeholder(EntityType::GlobalNonFungibleResourceManager, 8));
+    let governor = ComponentAddress::new_or_panic(placeholder(EntityType::GlobalGenericComponent, 9));
+    let variables: Vec<(GlobalAddress, &str)> = vec![
+        (account1.into(), "ACCOUNT1"),
+        (account2.into(), "ACCOUNT2"),
+        (package.into(), "PACKAGE"),
+        (offered.into(), "OFFERED"),
+        (requested.into(), "REQUESTED"),
+        (escrow.into(), "ESCROW"),
+        (escrow_badge.into(), "ESCROW_BADGE"),
+        (owner_badge.into(), "OWNER_BADGE_RESOURCE1"),
+        (governor.into(), "GOVERNOR"),
+    ];
+    // The local ID of the owner badge of account 1, known once resim creates the account
+    let owner_badge_id = NonFungibleLocalId::string("OWNER_BADGE_ID1").unwrap();
+    let rtm = |builder: ManifestBuilder| {
+        let object_names = builder.object_names();
+        let manifest = builder.build();
+        let mut text = decompile_with_known_naming(&manifest.instructions, &network, object_names)
+            .expect("Failed to decompile the manifest");
+        for (address, variable) in &variables {
+            let address = encoder
+                .encode(address.as_node_id().as_bytes())
+                .expect("Failed to encode the address");
+            text = text.replace(&format!("\"{}\"", address), &format!("\"${{{}}}\"", variable));
+        }
+        text.replace(&format!("\"{}\"", owner_badge_id), "\"${OWNER_BADGE_ID1}\"")
+    };
+    let fee = |account| manifests::lock_fee(ManifestBuilder::new(), manifests::FeeStrategy::Faucet, account, dec!("10"));
+
+    let offered = EscrowResourceSpecifier::Fungible { resource_address: offered, amount: offered_amount };
+    let requested = EscrowResourceSpecifier::Fungible { resource_address: requested, amount: requested_amount };
+    // The package is owned by account 1, whose owner badge authorizes naming the governor
+    let set_package_governor = rtm(manifests::set_package_governor(
+        fee(account1).create_proof_from_account_of_non_fungibles(account1, owner_badge, [owner_badge_id.clone()]),
+        package,
+        governor
+    ));
+    let instantiate_escrow = rtm(manifests::instantiate_escrow(
+        fee(account1), account1, package, &offered, vec![requested.clone()], EscrowOptions::default()
+    ));
+    let exchange = rtm(manifests::exchange(
+        fee(account2), account2, escrow, &requested, None, Some(&offered)
+    ));
+    let withdraw_resource = rtm(manifests::withdraw_resource(
+        fee(account1), account1, escrow, escrow_badge, NonFungibleLocalId::integer(DEFAULT_ESCROW_ID), Some(&requested)
+    ));
+
+    format!(r#"#!/usr/bin/env bash
+# Escrow demo on resim, written by `escrow resim-script`. Run it from the package directory, or
+# set PACKAGE_DIR. It resets the resim ledger.
+#
+# Account 1 offers {offered_amount} Offered for {requested_amount} Requested held by account 2,
+# account 2 takes the offer and account 1 withdraws the proceeds.
+
+set -euo pipefail
+export NO_COLOR=1
+
+PACKAGE_DIR="${{PACKAGE_DIR:-.}}"
+MANIFESTS="$(mktemp -d)"
+
+resim reset
+
+echo "Creating the accounts"
+OUTPUT="$(resim new-account)"
+ACCOUNT1="$(echo "$OUTPUT" | sed -n 's/^Account component address: //p')"
+PRIVATE_KEY1="$(echo "$OUTPUT" | sed -n 's/^Private key: //p')"
+OWNER_BADGE1="$(echo "$OUTPUT" | sed -n 's/^Owner badge: //p')"
+OUTPUT="$(resim new-account)"
+ACCOUNT2="$(echo "$OUTPUT" | sed -n 's/^Account component address: //p')"
+PRIVATE_KEY2="$(echo "$OUTPUT" | sed -n 's/^Private key: //p')"
+OWNER_BADGE2="$(echo "$OUTPUT" | sed -n 's/^Owner badge: //p')"
+echo "Account 1: $ACCOUNT1"
+echo "Account 2: $ACCOUNT2"
+
+echo "Publishing the package"
+PACKAGE="$(resim publish "$PACKAGE_DIR" | sed -n 's/.*New Package: //p')"
+echo "Package: $PACKAGE"
+
+echo "Account 1 names the governor of the escrows, as the package owner"
+GOVERNOR="$(resim call-function "$PACKAGE" EscrowGovernor instantiate_escrow_governor | sed -n 's/.*Component: //p' | head -n1)"
+OWNER_BADGE_RESOURCE1="${{OWNER_BADGE1%%:*}}"
+OWNER_BADGE_ID1="${{OWNER_BADGE1#*:}}"
+cat > "$MANIFESTS/set_package_governor.rtm" <<EOF
+{set_package_governor}EOF
+resim run "$MANIFESTS/set_package_governor.rtm" > /dev/null
+echo "Governor: $GOVERNOR"
+
+echo "Creating the tokens"
+resim set-default-account "$ACCOUNT1" "$PRIVATE_KEY1" "$OWNER_BADGE1"
+OFFERED="$(resim new-token-fixed --name Offered {offered_amount} | sed -n 's/.*Resource: //p' | head -n1)"
+resim set-default-account "$ACCOUNT2" "$PRIVATE_KEY2" "$OWNER_BADGE2"
+REQUESTED="$(resim new-token-fixed --name Requested {requested_amount} | sed -n 's/.*Resource: //p' | head -n1)"
+echo "Offered: $OFFERED"
+echo "Requested: $REQUESTED"
+
+echo "Account 1 instantiates the escrow"
+resim set-default-account "$ACCOUNT1" "$PRIVATE_KEY1" "$OWNER_BADGE1"
+cat > "$MANIFESTS/instantiate_escrow.rtm" <<EOF
+{instantiate_escrow}EOF
+OUTPUT="$(resim run "$MANIFESTS/instantiate_escrow.rtm")"
+ESCROW="$(echo "$OUTPUT" | sed -n 's/.*Component: //p' | head -n1)"
+ESCROW_BADGE="$(echo "$OUTPUT" | sed -n 's/.*Resource: //p' | head -n1)"
+echo "Escrow: $ESCROW"
+
+echo "Account 2 exchanges"
+resim set-default-account "$ACCOUNT2" "$PRIVATE_KEY2" "$OWNER_BADGE2"
+cat > "$MANIFESTS/exchange.rtm" <<EOF
+{exchange}EOF
+resim run "$MANIFESTS/exchange.rtm" > /dev/null
+
+echo "Account 1 withdraws the proceeds"
+resim set-default-account "$ACCOUNT1" "$PRIVATE_KEY1" "$OWNER_BADGE1"
+cat > "$MANIFESTS/withdraw_resource.rtm" <<EOF
+{withdraw_resource}EOF
+resim run "$MANIFESTS/withdraw_resource.rtm" > /dev/null
+
+resim show "$ACCOUNT1"
+resim show "$ACCOUNT2"
+"#)
+}
+
+// The ID of an entity of the type, distinct for each `index`
+fn placeholder(entity_type: EntityType, index: u8) -> [u8; NodeId::LENGTH] {
+    let mut node_id = [0u8; NodeId::LENGTH];
+    node_id[0] = entity_type as u8;
+    node_id[NodeId::LENGTH - 1] = index;
+    node_id
+}
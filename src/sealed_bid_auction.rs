@@ -0,0 +1,270 @@
+use scrypto::prelude::*;
+
+use crate::{assert_not_paused, create_escrow_badge, package_governor, EscrowResourceSpecifier};
+
+#[blueprint]
+mod sealed_bid_auction {
+    struct SealedBidAuction {
+        lot: Vault,
+        lot_info: EscrowResourceSpecifier,
+        payment: EscrowResourceSpecifier,
+        commit_deadline: Instant,
+        reveal_deadline: Instant,
+        bids: KeyValueStore<NonFungibleLocalId, SealedBid>,
+        // The receipt of every committed bid, as the bids can't be iterated
+        bid_receipts: Vec<NonFungibleLocalId>,
+        top_bid: Option<(NonFungibleLocalId, Decimal)>,
+        winning_bid: Option<NonFungibleLocalId>,
+        bid_receipt: ResourceAddress,
+        escrow_nft: ResourceAddress,
+        settled: bool,
+        // The governor of the package when the auction was instantiated
+        governor: ComponentAddress,
+    }
+
+    impl SealedBidAuction {
+
+        /// The `payment` specifier must be `Fungible`, its amount is the reserve price. Fails while
+        /// the governor of the package is paused.
+        pub fn instantiate_sealed_bid_auction(
+            lot: Bucket,
+            payment: EscrowResourceSpecifier,
+            commit_deadline: Instant,
+            reveal_deadline: Instant
+        ) -> (Global<SealedBidAuction>, NonFungibleBucket) {
+            assert!(!lot.is_empty(), "You must put a resource up for auction!");
+            assert!(
+                lot.resource_address().is_fungible() || lot.amount() == Decimal::ONE,
+                "A non-fungible lot must be a single NFT!"
+            );
+            assert!(
+                matches!(payment, EscrowResourceSpecifier::Fungible { .. }),
+                "Bids must be made in a fungible resource!"
+            );
+            assert!(
+                reveal_deadline.compare(commit_deadline, TimeComparisonOperator::Gt),
+                "The reveal deadline must be after the commit deadline!"
+            );
+            let governor = package_governor();
+            assert_not_paused(governor);
+
+            let (address_reservation, component_address) =
+                Runtime::allocate_component_address(SealedBidAuction::blueprint_id());
+
+            // The badge holder is the seller, allowed to settle the auction and collect the proceeds
+            let escrow_nft = create_escrow_badge(lot.resource_address());
+
+            // Every sealed bid is acknowledged with a receipt, used to reveal and to claim
+            let bid_receipt = ResourceBuilder::new_ruid_non_fungible::<SealedBidReceipt>(OwnerRole::None)
+                .metadata(metadata!(
+                    init {
+                        "name" => "Escrow sealed bid receipt", locked;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+
+            let lot = Vault::with_bucket(lot);
+            let auction = Self {
+                lot_info: EscrowResourceSpecifier::from_vault(&lot),
+                lot,
+                payment,
+                commit_deadline,
+                reveal_deadline,
+                bids: KeyValueStore::new(),
+                bid_receipts: Vec::new(),
+                top_bid: None,
+                winning_bid: None,
+                bid_receipt: bid_receipt.address(),
+                escrow_nft: escrow_nft.resource_address(),
+                settled: false,
+                governor,
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .with_address(address_reservation)
+            .globalize();
+
+            (auction, escrow_nft)
+        }
+
+        /// Commits to a bid with `sealed_bid_commitment(amount, salt)`. The deposit must cover
+        /// the amount that will be revealed. Bids that are never revealed forfeit their whole
+        /// deposit to the seller when the auction is settled, so committing to several bids and revealing only the one that
+        /// wins costs the deposits of the others.
+        pub fn commit_bid(&mut self, commitment: Hash, deposit: Bucket) -> NonFungibleBucket {
+            assert_not_paused(self.governor);
+            assert!(
+                Clock::current_time_is_strictly_before(self.commit_deadline, TimePrecision::Second),
+                "The commit phase is over!"
+            );
+            assert_eq!(
+                deposit.resource_address(),
+                self.payment.get_resource_address(),
+                "You must deposit the requested resource, invalid resource specified!"
+            );
+
+            let receipt = ResourceManager::from(self.bid_receipt)
+                .mint_ruid_non_fungible(SealedBidReceipt { commitment })
+                .as_non_fungible();
+            self.bid_receipts.push(receipt.non_fungible_local_id());
+            self.bids.insert(
+                receipt.non_fungible_local_id(),
+                SealedBid {
+                    deposit: Vault::with_bucket(deposit),
+                    commitment,
+                    revealed_amount: None,
+                },
+            );
+
+            receipt
+        }
+
+        /// Reveals a committed bid. Reveals that do not match the commitment are rejected, bids
+        /// under the reserve price or above the deposit are recorded but can't win.
+        pub fn reveal_bid(&mut self, bid_receipt: Proof, amount: Decimal, salt: String) {
+            assert!(
+                Clock::current_time_is_at_or_after(self.commit_deadline, TimePrecision::Second),
+                "The reveal phase has not started yet!"
+            );
+            assert!(
+                Clock::current_time_is_strictly_before(self.reveal_deadline, TimePrecision::Second),
+                "The reveal phase is over!"
+            );
+            let receipt_id = bid_receipt
+                .check(self.bid_receipt)
+                .as_non_fungible()
+                .non_fungible_local_id();
+
+            let reserve_price = self.reserve_price();
+            let valid = {
+                let mut bid = self.bids.get_mut(&receipt_id).unwrap();
+                assert!(bid.revealed_amount.is_none(), "This bid has already been revealed!");
+                assert_eq!(
+                    bid.commitment,
+                    sealed_bid_commitment(amount, &salt),
+                    "The revealed amount and salt do not match the commitment!"
+                );
+                bid.revealed_amount = Some(amount);
+
+                amount >= reserve_price && amount <= bid.deposit.amount()
+            };
+
+            // Ties go to the bid revealed first
+            let is_top_bid = match &self.top_bid {
+                Some((_, top_amount)) => amount > *top_amount,
+                None => true,
+            };
+            if valid && is_top_bid {
+                self.top_bid = Some((receipt_id, amount));
+            }
+        }
+
+        /// Settles the auction once the reveal phase is over. The highest valid bid is returned
+        /// to the badge holder together with the deposits of the bids that were never revealed,
+        /// and the lot is kept for the winner to claim. If no valid bid was revealed, the lot is
+        /// returned too.
+        pub fn settle(&mut self, escrow_nft: NonFungibleBucket) -> Vec<Bucket> {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to settle the auction"
+            );
+            assert!(
+                Clock::current_time_is_at_or_after(self.reveal_deadline, TimePrecision::Second),
+                "The reveal phase is not over yet!"
+            );
+            assert!(!self.settled, "The auction has already been settled!");
+
+            self.settled = true;
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            // Unrevealed bids forfeit their whole deposit
+            let mut proceeds = Bucket::new(self.payment.get_resource_address());
+            for receipt_id in &self.bid_receipts {
+                let mut bid = self.bids.get_mut(receipt_id).unwrap();
+                if bid.revealed_amount.is_none() {
+                    proceeds.put(bid.deposit.take_all());
+                }
+            }
+
+            match self.top_bid.take() {
+                Some((receipt_id, amount)) => {
+                    self.winning_bid = Some(receipt_id.clone());
+                    proceeds.put(self.bids.get_mut(&receipt_id).unwrap().deposit.take(amount));
+                    vec![proceeds]
+                }
+                None => vec![proceeds, self.lot.take_all()],
+            }
+        }
+
+        /// Returns the deposit of a revealed bid once the reveal phase is over, minus the winning
+        /// amount and together with the lot for the winner.
+        pub fn claim(&mut self, bid_receipt: NonFungibleBucket) -> Vec<Bucket> {
+            assert_eq!(
+                bid_receipt.resource_address(),
+                self.bid_receipt,
+                "You must provide a bid receipt of this auction"
+            );
+            assert!(
+                Clock::current_time_is_at_or_after(self.reveal_deadline, TimePrecision::Second),
+                "The reveal phase is not over yet!"
+            );
+            let receipt_id = bid_receipt.non_fungible_local_id();
+            assert!(
+                self.bids.get(&receipt_id).unwrap().revealed_amount.is_some(),
+                "This bid was never revealed, its deposit is forfeited to the seller!"
+            );
+            assert!(
+                self.settled || self.top_bid.as_ref().map(|(id, _)| id) != Some(&receipt_id),
+                "Your bid is the top bid, wait for the auction to be settled"
+            );
+
+            let mut claimed = vec![self.bids.get_mut(&receipt_id).unwrap().deposit.take_all()];
+            if self.winning_bid.as_ref() == Some(&receipt_id) {
+                claimed.push(self.lot.take_all());
+            }
+
+            bid_receipt.burn();
+
+            claimed
+        }
+
+        pub fn get_lot_info(&self) -> EscrowResourceSpecifier {
+            self.lot_info.clone()
+        }
+
+        fn reserve_price(&self) -> Decimal {
+            match &self.payment {
+                EscrowResourceSpecifier::Fungible { amount, .. } => *amount,
+                EscrowResourceSpecifier::NonFungible { .. } => unreachable!(),
+            }
+        }
+    }
+}
+
+#[derive(ScryptoSbor)]
+pub struct SealedBid {
+    deposit: Vault,
+    commitment: Hash,
+    revealed_amount: Option<Decimal>
+}
+
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct SealedBidReceipt {
+    pub commitment: Hash
+}
+
+/// The commitment a bidder submits for a sealed bid of `amount`, salted so it can't be guessed.
+pub fn sealed_bid_commitment(amount: Decimal, salt: &str) -> Hash {
+    hash(scrypto_encode(&(amount, salt.to_string())).unwrap())
+}
@@ -0,0 +1,142 @@
+use scrypto::prelude::*;
+
+use crate::{assert_not_paused, create_escrow_badge, package_governor, EscrowResourceSpecifier};
+
+#[blueprint]
+mod subscription_escrow {
+    struct SubscriptionEscrow {
+        payment: EscrowResourceSpecifier,
+        period_seconds: i64,
+        start: Instant,
+        collected_total: Decimal,
+        funds: Vault,
+        provider_vault: Vault,
+        provider_badge: ResourceAddress,
+        escrow_nft: ResourceAddress,
+        cancelled: bool,
+        // The governor of the package when the subscription was instantiated
+        governor: ComponentAddress,
+    }
+
+    impl SubscriptionEscrow {
+
+        /// The `payment` specifier must be `Fungible`, the provider holding a `provider_badge`
+        /// can collect its amount once per period, starting with the current period. Fails while
+        /// the governor of the package is paused.
+        pub fn instantiate_subscription_escrow(
+            payment: EscrowResourceSpecifier,
+            period_seconds: i64,
+            provider_badge: ResourceAddress,
+            funds: Bucket
+        ) -> (Global<SubscriptionEscrow>, NonFungibleBucket) {
+            match &payment {
+                EscrowResourceSpecifier::Fungible { amount, .. } => {
+                    assert!(*amount > Decimal::ZERO, "The payment amount must be positive!");
+                }
+                EscrowResourceSpecifier::NonFungible { .. } => {
+                    panic!("Subscriptions must be paid in a fungible resource!");
+                }
+            }
+            assert!(period_seconds > 0, "The period must be positive!");
+            let governor = package_governor();
+            assert_not_paused(governor);
+            assert_eq!(
+                funds.resource_address(),
+                payment.get_resource_address(),
+                "You must fund the subscription with the payment resource, invalid resource specified!"
+            );
+
+            // The badge holder is the subscriber, allowed to cancel and reclaim the unspent funds
+            let escrow_nft = create_escrow_badge(funds.resource_address());
+
+            let escrow = Self {
+                provider_vault: Vault::new(funds.resource_address()),
+                funds: Vault::with_bucket(funds),
+                payment,
+                period_seconds,
+                start: Clock::current_time_rounded_to_seconds(),
+                collected_total: Decimal::ZERO,
+                provider_badge,
+                escrow_nft: escrow_nft.resource_address(),
+                cancelled: false,
+                governor,
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize();
+
+            (escrow, escrow_nft)
+        }
+
+        pub fn top_up(&mut self, funds: Bucket) {
+            assert_not_paused(self.governor);
+            assert!(!self.cancelled, "The subscription has been cancelled!");
+            self.funds.put(funds);
+        }
+
+        /// Collects the payments of every period started so far that the funds can cover.
+        pub fn collect(&mut self, provider_badge: Proof) -> Bucket {
+            assert_not_paused(self.governor);
+            provider_badge.check(self.provider_badge);
+
+            let due = self.collectable_amount();
+            self.collected_total += due;
+
+            let mut payment = self.provider_vault.take_all();
+            payment.put(self.funds.take(due));
+
+            payment
+        }
+
+        /// Cancels the subscription, keeping what the provider is owed so far for them to collect
+        /// and returning the rest of the funds.
+        pub fn cancel_subscription(&mut self, escrow_nft: NonFungibleBucket) -> Bucket {
+            // Assert that the caller is authorized by checking the NFT
+            assert_eq!(
+                escrow_nft.resource_address(),
+                self.escrow_nft,
+                "You must provide the correct escrow NFT to cancel the subscription"
+            );
+            assert!(!self.cancelled, "The subscription has already been cancelled!");
+
+            let due = self.collectable_amount();
+            self.collected_total += due;
+            self.provider_vault.put(self.funds.take(due));
+            self.cancelled = true;
+
+            // Burn the escrow NFT to ensure it can't be used again
+            escrow_nft.burn();
+
+            self.funds.take_all()
+        }
+
+        pub fn get_collectable_amount(&self) -> Decimal {
+            self.provider_vault.amount() + self.collectable_amount()
+        }
+
+        pub fn get_remaining_funds(&self) -> Decimal {
+            self.funds.amount() - self.collectable_amount()
+        }
+
+        // The payments of the periods started so far which have not been collected, limited to
+        // the whole periods the funds can cover
+        fn collectable_amount(&self) -> Decimal {
+            if self.cancelled {
+                return Decimal::ZERO;
+            }
+            let EscrowResourceSpecifier::Fungible { amount, .. } = &self.payment else {
+                unreachable!()
+            };
+
+            let elapsed = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch
+                - self.start.seconds_since_unix_epoch;
+            let periods_started = elapsed / self.period_seconds + 1;
+            let due = Decimal::from(periods_started) * *amount - self.collected_total;
+            let affordable = (self.funds.amount() / *amount)
+                .checked_floor()
+                .unwrap() * *amount;
+
+            due.min(affordable)
+        }
+    }
+}
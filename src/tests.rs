@@ -1,7 +1,10 @@
+use clap::Parser;
 use scrypto::prelude::*;
 use scrypto_test::prelude::*;
 
-use crate::{escrow::Escrow, EscrowBadge, EscrowResourceSpecifier, DEFAULT_ESCROW_ID};
+use crate::cli::{generate, Cli};
+use crate::profiles::NetworkProfile;
+use crate::{dry_run, escrow::Escrow, explainer, manifests, resim, sealed_bid_commitment, EscrowBadge, EscrowCallback, EscrowFill, EscrowOptions, EscrowResourceSpecifier, OraclePricing, SealedBidReceipt, TakerReceipt, DEFAULT_ESCROW_ID, MOCK_ESCROW_CALLBACK_METHOD};
 
 struct TestEnvironment {
     ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
@@ -14,6 +17,8 @@ struct TestEnvironment {
     requested_resource: ResourceAddress,
     offered_nft: ResourceAddress,
     requested_nft: ResourceAddress,
+    governor: ComponentAddress,
+    governor_admin_badge: ResourceAddress,
 }
 
 fn setup() -> TestEnvironment {
@@ -24,8 +29,25 @@ fn setup() -> TestEnvironment {
     let (public_key1, _private_key1, account1) = ledger.new_allocated_account();
     let (public_key2, _private_key2, account2) = ledger.new_allocated_account();
 
-    // Publish package
-    let package_address = ledger.compile_and_publish(this_package!());
+    // Publish package, owned by the first account which names the governor of its escrows
+    let owner_badge = NonFungibleGlobalId::from_public_key(&public_key1);
+    let package_address = ledger.compile_and_publish_with_owner(this_package!(), owner_badge.clone());
+    let manifest = manifests::instantiate_escrow_governor(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        account1,
+        package_address
+    )
+    .build();
+    let receipt = ledger.execute_manifest(manifest, vec![owner_badge.clone()]);
+    let result = receipt.expect_commit_success();
+    let (governor, governor_admin_badge) = (result.new_component_addresses()[0], result.new_resource_addresses()[0]);
+    let manifest = manifests::set_package_governor(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        package_address,
+        governor
+    )
+    .build();
+    ledger.execute_manifest(manifest, vec![owner_badge]).expect_commit_success();
 
     // Create test resources
     let offered_resource = ledger.create_fungible_resource(dec!("100"), 0, account1);
@@ -46,31 +68,48 @@ fn setup() -> TestEnvironment {
         requested_resource,
         offered_nft,
         requested_nft,
+        governor,
+        governor_admin_badge,
     }
 }
 
 fn instantiate_escrow(env: &mut TestEnvironment, requested: EscrowResourceSpecifier, offered_resource: ResourceAddress, amount: Decimal) -> (ComponentAddress, ResourceAddress) {
-    let manifest = ManifestBuilder::new()
-        .lock_fee_from_faucet()
-        .withdraw_from_account(env.account1, offered_resource, amount)
-        .take_all_from_worktop(offered_resource, "offered_bucket")
-        .call_function_with_name_lookup(
-            env.package_address,
-            "Escrow",
-            "instantiate_escrow",
-            |lookup| (
-                requested,
-                lookup.bucket("offered_bucket")
-            )
-        )
-        .deposit_batch(env.account1)
-        .build();
+    instantiate_escrow_with_options(env, vec![requested], offered_resource, amount, EscrowOptions::default())
+}
+
+fn instantiate_escrow_with_alternatives(env: &mut TestEnvironment, requested: Vec<EscrowResourceSpecifier>, offered_resource: ResourceAddress, amount: Decimal) -> (ComponentAddress, ResourceAddress) {
+    instantiate_escrow_with_options(env, requested, offered_resource, amount, EscrowOptions::default())
+}
+
+fn instantiate_escrow_with_options(env: &mut TestEnvironment, requested: Vec<EscrowResourceSpecifier>, offered_resource: ResourceAddress, amount: Decimal, options: EscrowOptions) -> (ComponentAddress, ResourceAddress) {
+    // Offered non-fungibles are the ones minted by `create_non_fungible_resource`, from #1#
+    let offered = if offered_resource.is_fungible() {
+        EscrowResourceSpecifier::Fungible { resource_address: offered_resource, amount }
+    } else {
+        EscrowResourceSpecifier::NonFungible { resource_address: offered_resource, non_fungible_local_id: NonFungibleLocalId::integer(1) }
+    };
+    let manifest = manifests::instantiate_escrow(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        env.account1,
+        env.package_address,
+        &offered,
+        requested,
+        options,
+    )
+    .build();
     let receipt = env.ledger.execute_manifest(
         manifest,
         vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
     );
     let result = receipt.expect_commit(true);
-    (result.new_component_addresses()[0], result.new_resource_addresses()[0])
+    // The escrow may come with an account locker, pick the escrow component itself
+    let component = result
+        .new_component_addresses()
+        .iter()
+        .find(|address| address.as_node_id().entity_type() == Some(EntityType::GlobalGenericComponent))
+        .copied()
+        .unwrap();
+    (component, result.new_resource_addresses()[0])
 }
 
 #[test]
@@ -113,6 +152,8 @@ fn test_successful_exchange() {
             "exchange",
             |lookup| (
                 lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
             )
         )
         .deposit_batch(env.account2)
@@ -145,6 +186,8 @@ fn test_withdraw_resource() {
             "exchange",
             |lookup| (
                 lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
             )
         )
         .deposit_batch(env.account2)
@@ -226,6 +269,8 @@ fn test_exchange_with_incorrect_amount() {
             "exchange",
             |lookup| (
                 lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
             )
         )
         .deposit_batch(env.account2)
@@ -371,21 +416,10 @@ fn perform_exchange(env: &mut TestEnvironment, component: ComponentAddress, reso
         .lock_fee_from_faucet();
 
     let manifest = match amount_or_id.into() {
-        AmountOrId::Amount(amount) => manifest.withdraw_from_account(env.account2, resource, amount),
-        AmountOrId::Id(id) => manifest.withdraw_non_fungibles_from_account(env.account2, resource, [id]),
-    };
-
-    let manifest = manifest
-        .take_all_from_worktop(resource, "requested_bucket")
-        .call_method_with_name_lookup(
-            component,
-            "exchange",
-            |lookup| (
-                lookup.bucket("requested_bucket"),
-            )
-        )
-        .deposit_batch(env.account2)
-        .build();
+        AmountOrId::Amount(amount) => manifests::exchange_fungible(manifest, env.account2, component, resource, amount, None, None),
+        AmountOrId::Id(id) => manifests::exchange_non_fungible(manifest, env.account2, component, resource, id, None, None),
+    }
+    .build();
 
     let receipt = env.ledger.execute_manifest(
         manifest,
@@ -427,22 +461,2313 @@ impl From<NonFungibleLocalId> for AmountOrId {
 }
 
 fn withdraw_resource(env: &mut TestEnvironment, component: ComponentAddress, escrow_nft: ResourceAddress) {
+    let manifest = manifests::withdraw_resource(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        env.account1,
+        component,
+        escrow_nft,
+        NonFungibleLocalId::integer(DEFAULT_ESCROW_ID),
+        None,
+    )
+    .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    receipt.expect_commit_success();
+}
+
+// English auction
+
+// Auctions created by `instantiate_english_auction` end this long after their creation
+const ENGLISH_AUCTION_SECONDS: i64 = 60;
+
+fn instantiate_english_auction(env: &mut TestEnvironment, reserve_price: Decimal, min_increment: Decimal) -> (ComponentAddress, ResourceAddress, ResourceAddress) {
+    let end = env.ledger.get_current_time(TimePrecision::Second).add_seconds(ENGLISH_AUCTION_SECONDS).unwrap();
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account1, env.offered_nft, [NonFungibleLocalId::integer(1)])
+        .take_all_from_worktop(env.offered_nft, "lot")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "EnglishAuction",
+            "instantiate_english_auction",
+            |lookup| (
+                lookup.bucket("lot"),
+                XRD,
+                reserve_price,
+                min_increment,
+                end,
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    let result = receipt.expect_commit(true);
+    (result.new_component_addresses()[0], result.new_resource_addresses()[0], result.new_resource_addresses()[1])
+}
+
+fn place_bid(env: &mut TestEnvironment, component: ComponentAddress, account: ComponentAddress, public_key: Secp256k1PublicKey, amount: Decimal) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(account, XRD, amount)
+        .take_all_from_worktop(XRD, "bid")
+        .call_method_with_name_lookup(
+            component,
+            "bid",
+            |lookup| (
+                lookup.bucket("bid"),
+                Some(account),
+            )
+        )
+        .deposit_batch(account)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    )
+}
+
+#[test]
+fn test_auction_outbid_refunds_previous_bidder() {
+    let mut env = setup();
+    let (public_key3, _private_key3, account3) = env.ledger.new_allocated_account();
+    let (component, _, _) = instantiate_english_auction(&mut env, dec!("100"), dec!("10"));
+
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+    let balance_before = env.ledger.get_component_balance(account2, XRD);
+
+    place_bid(&mut env, component, account2, public_key2, dec!("100")).expect_commit_success();
+    place_bid(&mut env, component, account3, public_key3, dec!("110")).expect_commit_success();
+
+    // The first bidder got their bid back when they were outbid
+    assert_eq!(env.ledger.get_component_balance(account2, XRD), balance_before);
+}
+
+#[test]
+fn test_auction_rejects_low_bids() {
+    let mut env = setup();
+    let (public_key3, _private_key3, account3) = env.ledger.new_allocated_account();
+    let (component, _, _) = instantiate_english_auction(&mut env, dec!("100"), dec!("10"));
+
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+
+    // Below the reserve price
+    place_bid(&mut env, component, account2, public_key2, dec!("99")).expect_commit_failure();
+
+    place_bid(&mut env, component, account2, public_key2, dec!("100")).expect_commit_success();
+
+    // Below the minimum increment
+    place_bid(&mut env, component, account3, public_key3, dec!("105")).expect_commit_failure();
+}
+
+fn end_english_auction(env: &mut TestEnvironment) {
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + ENGLISH_AUCTION_SECONDS) * 1000);
+}
+
+fn close_english_auction(env: &mut TestEnvironment, component: ComponentAddress, public_key: Secp256k1PublicKey) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(component, "close_auction", manifest_args!())
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    )
+}
+
+fn withdraw_auction_proceeds(env: &mut TestEnvironment, component: ComponentAddress, escrow_nft: ResourceAddress) {
     let manifest = ManifestBuilder::new()
         .lock_fee_from_faucet()
         .withdraw_non_fungibles_from_account(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
         .take_all_from_worktop(escrow_nft, "escrow_nft")
         .call_method_with_name_lookup(
             component,
-            "withdraw_resource",
+            "withdraw_proceeds",
             |lookup| (
                 lookup.bucket("escrow_nft"),
             )
         )
         .deposit_batch(env.account1)
         .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+}
+
+#[test]
+fn test_auction_closed_by_anyone_after_end() {
+    let mut env = setup();
+    let (public_key3, _private_key3, account3) = env.ledger.new_allocated_account();
+    let (component, escrow_nft, _) = instantiate_english_auction(&mut env, dec!("100"), dec!("10"));
+
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+    place_bid(&mut env, component, account2, public_key2, dec!("100")).expect_commit_success();
+    place_bid(&mut env, component, account3, public_key3, dec!("150")).expect_commit_success();
+
+    // Nobody can close the auction before its end, nor bid after it
+    close_english_auction(&mut env, component, public_key3).expect_commit_failure();
+    end_english_auction(&mut env);
+    place_bid(&mut env, component, account2, public_key2, dec!("200")).expect_commit_failure();
+
+    // The winner closes it without waiting on the seller
+    close_english_auction(&mut env, component, public_key3).expect_commit_success();
+    assert_eq!(env.ledger.get_component_balance(account3, env.offered_nft), dec!("1"));
+
+    let seller_balance_before = env.ledger.get_component_balance(env.account1, XRD);
+    withdraw_auction_proceeds(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), seller_balance_before + dec!("150"));
+}
+
+#[test]
+fn test_auction_seller_withdraw_closes_after_end() {
+    let mut env = setup();
+    let (public_key3, _private_key3, account3) = env.ledger.new_allocated_account();
+    let (component, escrow_nft, _) = instantiate_english_auction(&mut env, dec!("100"), dec!("10"));
+
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+    place_bid(&mut env, component, account2, public_key2, dec!("100")).expect_commit_success();
+    place_bid(&mut env, component, account3, public_key3, dec!("150")).expect_commit_success();
+    end_english_auction(&mut env);
+
+    let seller_balance_before = env.ledger.get_component_balance(env.account1, XRD);
+    withdraw_auction_proceeds(&mut env, component, escrow_nft);
+
+    // The seller received the winning bid and the winner received the lot
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), seller_balance_before + dec!("150"));
+    assert_eq!(env.ledger.get_component_balance(account3, env.offered_nft), dec!("1"));
+}
+
+
+// Sealed-bid auction
+
+fn instantiate_sealed_bid_auction(env: &mut TestEnvironment, reserve_price: Decimal, commit_deadline: Instant, reveal_deadline: Instant) -> (ComponentAddress, ResourceAddress, ResourceAddress) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account1, env.offered_nft, [NonFungibleLocalId::integer(1)])
+        .take_all_from_worktop(env.offered_nft, "lot")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "SealedBidAuction",
+            "instantiate_sealed_bid_auction",
+            |lookup| (
+                lookup.bucket("lot"),
+                EscrowResourceSpecifier::Fungible { resource_address: XRD, amount: reserve_price },
+                commit_deadline,
+                reveal_deadline,
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
     let receipt = env.ledger.execute_manifest(
         manifest,
         vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
     );
-    receipt.expect_commit_success();
-}
\ No newline at end of file
+    let result = receipt.expect_commit(true);
+    (result.new_component_addresses()[0], result.new_resource_addresses()[0], result.new_resource_addresses()[1])
+}
+
+fn commit_sealed_bid_manifest(component: ComponentAddress, account: ComponentAddress, deposit: Decimal, commitment: Hash) -> TransactionManifestV1 {
+    ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(account, XRD, deposit)
+        .take_all_from_worktop(XRD, "deposit")
+        .call_method_with_name_lookup(
+            component,
+            "commit_bid",
+            |lookup| (
+                commitment,
+                lookup.bucket("deposit"),
+            )
+        )
+        .deposit_batch(account)
+        .build()
+}
+
+fn commit_sealed_bid(env: &mut TestEnvironment, component: ComponentAddress, account: ComponentAddress, public_key: Secp256k1PublicKey, deposit: Decimal, commitment: Hash) {
+    let manifest = commit_sealed_bid_manifest(component, account, deposit, commitment);
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    ).expect_commit_success();
+}
+
+fn reveal_sealed_bid(env: &mut TestEnvironment, component: ComponentAddress, bid_receipt: ResourceAddress, account: ComponentAddress, public_key: Secp256k1PublicKey, amount: Decimal, salt: &str) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(account, bid_receipt, dec!("1"))
+        .pop_from_auth_zone("bid_receipt")
+        .call_method_with_name_lookup(
+            component,
+            "reveal_bid",
+            |lookup| (
+                lookup.proof("bid_receipt"),
+                amount,
+                salt.to_string(),
+            )
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    )
+}
+
+#[test]
+fn test_sealed_bid_auction_settles_with_highest_reveal() {
+    let mut env = setup();
+    let (public_key3, _private_key3, account3) = env.ledger.new_allocated_account();
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    let commit_deadline = now.add_seconds(60).unwrap();
+    let reveal_deadline = now.add_seconds(120).unwrap();
+    let (component, escrow_nft, bid_receipt) = instantiate_sealed_bid_auction(&mut env, dec!("100"), commit_deadline, reveal_deadline);
+
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+    let balance2_before = env.ledger.get_component_balance(account2, XRD);
+    let balance3_before = env.ledger.get_component_balance(account3, XRD);
+
+    commit_sealed_bid(&mut env, component, account2, public_key2, dec!("200"), sealed_bid_commitment(dec!("120"), "salt2"));
+    commit_sealed_bid(&mut env, component, account3, public_key3, dec!("300"), sealed_bid_commitment(dec!("150"), "salt3"));
+
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 60) * 1000);
+    reveal_sealed_bid(&mut env, component, bid_receipt, account2, public_key2, dec!("120"), "salt2").expect_commit_success();
+    reveal_sealed_bid(&mut env, component, bid_receipt, account3, public_key3, dec!("150"), "salt3").expect_commit_success();
+
+    env.ledger.advance_to_round_at_timestamp(Round::of(3), (now.seconds_since_unix_epoch + 120) * 1000);
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .take_all_from_worktop(escrow_nft, "escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            "settle",
+            |lookup| (
+                lookup.bucket("escrow_nft"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+
+    for (account, public_key) in [(account2, public_key2), (account3, public_key3)] {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(account, bid_receipt, dec!("1"))
+            .take_all_from_worktop(bid_receipt, "bid_receipt")
+            .call_method_with_name_lookup(
+                component,
+                "claim",
+                |lookup| (
+                    lookup.bucket("bid_receipt"),
+                )
+            )
+            .deposit_batch(account)
+            .build();
+        env.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        ).expect_commit_success();
+    }
+
+    // The losing bidder got their whole deposit back, the winner paid their revealed bid
+    assert_eq!(env.ledger.get_component_balance(account2, XRD), balance2_before);
+    assert_eq!(env.ledger.get_component_balance(account3, XRD), balance3_before - dec!("150"));
+    assert_eq!(env.ledger.get_component_balance(account3, env.offered_nft), dec!("1"));
+}
+
+#[test]
+fn test_sealed_bid_reveal_must_match_commitment() {
+    let mut env = setup();
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    let commit_deadline = now.add_seconds(60).unwrap();
+    let reveal_deadline = now.add_seconds(120).unwrap();
+    let (component, _, bid_receipt) = instantiate_sealed_bid_auction(&mut env, dec!("100"), commit_deadline, reveal_deadline);
+
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+    commit_sealed_bid(&mut env, component, account2, public_key2, dec!("200"), sealed_bid_commitment(dec!("120"), "salt2"));
+
+    // Revealing during the commit phase is not allowed
+    reveal_sealed_bid(&mut env, component, bid_receipt, account2, public_key2, dec!("120"), "salt2").expect_commit_failure();
+
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 60) * 1000);
+    reveal_sealed_bid(&mut env, component, bid_receipt, account2, public_key2, dec!("110"), "salt2").expect_commit_failure();
+    reveal_sealed_bid(&mut env, component, bid_receipt, account2, public_key2, dec!("120"), "salt2").expect_commit_success();
+}
+
+
+#[test]
+fn test_sealed_bid_unrevealed_deposit_forfeited() {
+    let mut env = setup();
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    let commit_deadline = now.add_seconds(60).unwrap();
+    let reveal_deadline = now.add_seconds(120).unwrap();
+    let (component, escrow_nft, bid_receipt) = instantiate_sealed_bid_auction(&mut env, dec!("100"), commit_deadline, reveal_deadline);
+
+    // Account2 commits to two bids and only reveals the first one
+    let account2 = env.account2;
+    let public_key2 = env.public_key2;
+    let unrevealed_commitment = sealed_bid_commitment(dec!("180"), "salt_high");
+    commit_sealed_bid(&mut env, component, account2, public_key2, dec!("200"), sealed_bid_commitment(dec!("120"), "salt_low"));
+    commit_sealed_bid(&mut env, component, account2, public_key2, dec!("200"), unrevealed_commitment);
+
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 60) * 1000);
+    reveal_sealed_bid(&mut env, component, bid_receipt, account2, public_key2, dec!("120"), "salt_low").expect_commit_success();
+    env.ledger.advance_to_round_at_timestamp(Round::of(3), (now.seconds_since_unix_epoch + 120) * 1000);
+
+    let vault = env.ledger.get_component_vaults(account2, bid_receipt)[0];
+    let (_, ids) = env.ledger.inspect_non_fungible_vault(vault).unwrap();
+    let ids: Vec<NonFungibleLocalId> = ids.collect();
+    let unrevealed_id = ids
+        .iter()
+        .find(|id| env.ledger.get_non_fungible_data::<SealedBidReceipt>(bid_receipt, (*id).clone()).commitment == unrevealed_commitment)
+        .unwrap()
+        .clone();
+
+    // The unrevealed bid can't be claimed back
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(account2, bid_receipt, [unrevealed_id.clone()])
+        .take_all_from_worktop(bid_receipt, "bid_receipt")
+        .call_method_with_name_lookup(
+            component,
+            "claim",
+            |lookup| (
+                lookup.bucket("bid_receipt"),
+            )
+        )
+        .deposit_batch(account2)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key2)],
+    ).expect_commit_failure();
+
+    // The seller settles without knowing which bids were left unrevealed, and collects their
+    // deposits together with the winning amount
+    let balance1_before = env.ledger.get_component_balance(env.account1, XRD);
+    let manifest = ManifestBuilder::new()
+        .lock_fee(env.account2, dec!("10"))
+        .withdraw_non_fungibles_from_account(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .take_all_from_worktop(escrow_nft, "escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            "settle",
+            |lookup| (
+                lookup.bucket("escrow_nft"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![
+            NonFungibleGlobalId::from_public_key(&env.public_key1),
+            NonFungibleGlobalId::from_public_key(&public_key2),
+        ],
+    ).expect_commit_success();
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), balance1_before + dec!("320"));
+}
+
+#[test]
+fn test_sealed_bid_auction_rejects_several_nfts_as_lot() {
+    let mut env = setup();
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account1, env.offered_nft, [NonFungibleLocalId::integer(1), NonFungibleLocalId::integer(2)])
+        .take_all_from_worktop(env.offered_nft, "lot")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "SealedBidAuction",
+            "instantiate_sealed_bid_auction",
+            |lookup| (
+                lookup.bucket("lot"),
+                EscrowResourceSpecifier::Fungible { resource_address: XRD, amount: dec!("100") },
+                now.add_seconds(60).unwrap(),
+                now.add_seconds(120).unwrap(),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_failure();
+}
+
+// Oracle-priced escrow
+
+fn instantiate_mock_price_oracle(env: &mut TestEnvironment) -> ComponentAddress {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_function(
+            env.package_address,
+            "MockPriceOracle",
+            "instantiate_mock_price_oracle",
+            manifest_args!(),
+        )
+        .build();
+    let receipt = env.ledger.execute_manifest(manifest, vec![]);
+    receipt.expect_commit(true).new_component_addresses()[0]
+}
+
+fn set_oracle_price(env: &mut TestEnvironment, oracle: ComponentAddress, price: Decimal) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(
+            oracle,
+            "set_price",
+            manifest_args!(env.offered_resource, env.requested_resource, price),
+        )
+        .build();
+    env.ledger.execute_manifest(manifest, vec![]).expect_commit_success();
+}
+
+// Offers 10 of the offered resource at the oracle price minus 20%, expecting a price of 5
+fn instantiate_oracle_escrow(env: &mut TestEnvironment, oracle: ComponentAddress) -> ComponentAddress {
+    let pricing = OraclePricing {
+        oracle,
+        price_adjustment: dec!("-0.2"),
+        reference_price: dec!("5"),
+        max_slippage: dec!("0.1"),
+        max_price_age_seconds: 60,
+    };
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account1, env.offered_resource, dec!("10"))
+        .take_all_from_worktop(env.offered_resource, "offered_bucket")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "OracleEscrow",
+            "instantiate_oracle_escrow",
+            |lookup| (
+                lookup.bucket("offered_bucket"),
+                env.requested_resource,
+                pricing,
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    receipt.expect_commit(true).new_component_addresses()[0]
+}
+
+fn oracle_exchange_manifest(env: &TestEnvironment, component: ComponentAddress, amount: Decimal) -> TransactionManifestV1 {
+    ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, env.requested_resource, amount)
+        .take_all_from_worktop(env.requested_resource, "payment")
+        .call_method_with_name_lookup(
+            component,
+            "exchange",
+            |lookup| (
+                lookup.bucket("payment"),
+            )
+        )
+        .deposit_batch(env.account2)
+        .build()
+}
+
+fn perform_oracle_exchange(env: &mut TestEnvironment, component: ComponentAddress, amount: Decimal) -> TransactionReceipt {
+    let manifest = oracle_exchange_manifest(env, component, amount);
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    )
+}
+
+#[test]
+fn test_oracle_exchange_charges_adjusted_price() {
+    let mut env = setup();
+    let oracle = instantiate_mock_price_oracle(&mut env);
+    set_oracle_price(&mut env, oracle, dec!("5"));
+    let component = instantiate_oracle_escrow(&mut env, oracle);
+
+    perform_oracle_exchange(&mut env, component, dec!("50")).expect_commit_success();
+
+    // 10 units at 5 minus 20% cost 40, the change is returned
+    assert_eq!(env.ledger.get_component_balance(env.account2, env.requested_resource), dec!("60"));
+    assert_eq!(env.ledger.get_component_balance(env.account2, env.offered_resource), dec!("10"));
+}
+
+#[test]
+fn test_oracle_exchange_rejects_slippage() {
+    let mut env = setup();
+    let oracle = instantiate_mock_price_oracle(&mut env);
+    set_oracle_price(&mut env, oracle, dec!("5"));
+    let component = instantiate_oracle_escrow(&mut env, oracle);
+
+    // A price 20% above the reference price is outside the 10% bound
+    set_oracle_price(&mut env, oracle, dec!("6"));
+    perform_oracle_exchange(&mut env, component, dec!("50")).expect_commit_failure();
+}
+
+#[test]
+fn test_oracle_exchange_rejects_stale_price() {
+    let mut env = setup();
+    let oracle = instantiate_mock_price_oracle(&mut env);
+    set_oracle_price(&mut env, oracle, dec!("5"));
+    let component = instantiate_oracle_escrow(&mut env, oracle);
+
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 61) * 1000);
+    perform_oracle_exchange(&mut env, component, dec!("50")).expect_commit_failure();
+}
+
+
+// Bonded escrow
+
+fn instantiate_bonded_escrow(env: &mut TestEnvironment, dispute_period_seconds: i64) -> (ComponentAddress, ResourceAddress, ResourceAddress) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account1, env.offered_resource, dec!("10"))
+        .take_all_from_worktop(env.offered_resource, "offered_bucket")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "BondedEscrow",
+            "instantiate_bonded_escrow",
+            |lookup| (
+                EscrowResourceSpecifier::Fungible { resource_address: env.requested_resource, amount: dec!("10") },
+                EscrowResourceSpecifier::Fungible { resource_address: XRD, amount: dec!("100") },
+                lookup.bucket("offered_bucket"),
+                dispute_period_seconds,
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    let result = receipt.expect_commit(true);
+    (result.new_component_addresses()[0], result.new_resource_addresses()[0], result.new_resource_addresses()[1])
+}
+
+fn bonded_exchange_manifest(env: &TestEnvironment, component: ComponentAddress) -> TransactionManifestV1 {
+    ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, env.requested_resource, dec!("10"))
+        .take_all_from_worktop(env.requested_resource, "requested_bucket")
+        .withdraw_from_account(env.account2, XRD, dec!("100"))
+        .take_all_from_worktop(XRD, "bond")
+        .call_method_with_name_lookup(
+            component,
+            "exchange",
+            |lookup| (
+                lookup.bucket("requested_bucket"),
+                lookup.bucket("bond"),
+            )
+        )
+        .deposit_batch(env.account2)
+        .build()
+}
+
+fn perform_bonded_exchange(env: &mut TestEnvironment, component: ComponentAddress) {
+    let manifest = bonded_exchange_manifest(env, component);
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_success();
+}
+
+fn call_with_escrow_nft_proof(env: &mut TestEnvironment, component: ComponentAddress, escrow_nft: ResourceAddress, method: &str) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .pop_from_auth_zone("escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            method,
+            |lookup| (
+                lookup.proof("escrow_nft"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    )
+}
+
+fn reclaim_bond(env: &mut TestEnvironment, component: ComponentAddress, taker_badge: ResourceAddress) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, taker_badge, dec!("1"))
+        .take_all_from_worktop(taker_badge, "taker_badge")
+        .call_method_with_name_lookup(
+            component,
+            "reclaim_bond",
+            |lookup| (
+                lookup.bucket("taker_badge"),
+            )
+        )
+        .deposit_batch(env.account2)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    )
+}
+
+#[test]
+fn test_bond_returned_after_confirmation() {
+    let mut env = setup();
+    let (component, escrow_nft, taker_badge) = instantiate_bonded_escrow(&mut env, 3600);
+    let balance_before = env.ledger.get_component_balance(env.account2, XRD);
+
+    perform_bonded_exchange(&mut env, component);
+
+    // The bond is locked until the delivery is confirmed
+    reclaim_bond(&mut env, component, taker_badge).expect_commit_failure();
+
+    call_with_escrow_nft_proof(&mut env, component, escrow_nft, "confirm_delivery").expect_commit_success();
+    reclaim_bond(&mut env, component, taker_badge).expect_commit_success();
+
+    assert_eq!(env.ledger.get_component_balance(env.account2, XRD), balance_before);
+}
+
+#[test]
+fn test_disputed_bond_slashed_after_deadline() {
+    let mut env = setup();
+    let (component, escrow_nft, taker_badge) = instantiate_bonded_escrow(&mut env, 3600);
+    let balance_before = env.ledger.get_component_balance(env.account1, XRD);
+
+    perform_bonded_exchange(&mut env, component);
+    call_with_escrow_nft_proof(&mut env, component, escrow_nft, "open_dispute").expect_commit_success();
+
+    // The bond can't be slashed before the deadline
+    call_with_escrow_nft_proof(&mut env, component, escrow_nft, "slash_bond").expect_commit_failure();
+
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 3600) * 1000);
+
+    reclaim_bond(&mut env, component, taker_badge).expect_commit_failure();
+    call_with_escrow_nft_proof(&mut env, component, escrow_nft, "slash_bond").expect_commit_success();
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), balance_before + dec!("100"));
+}
+
+#[test]
+fn test_disputed_bond_slashed_before_withdraw() {
+    let mut env = setup();
+    let (component, escrow_nft, _) = instantiate_bonded_escrow(&mut env, 3600);
+    let balance_before = env.ledger.get_component_balance(env.account1, XRD);
+
+    perform_bonded_exchange(&mut env, component);
+    call_with_escrow_nft_proof(&mut env, component, escrow_nft, "open_dispute").expect_commit_success();
+
+    // Burning the badge now would lock the disputed bond forever
+    let manifest = manifests::withdraw_resource(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        env.account1,
+        component,
+        escrow_nft,
+        NonFungibleLocalId::integer(DEFAULT_ESCROW_ID),
+        None,
+    )
+    .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_failure();
+
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 3600) * 1000);
+    call_with_escrow_nft_proof(&mut env, component, escrow_nft, "slash_bond").expect_commit_success();
+    withdraw_resource(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), balance_before + dec!("100"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, env.requested_resource), dec!("10"));
+}
+
+#[test]
+fn test_undisputed_bond_returned_after_deadline() {
+    let mut env = setup();
+    let (component, _, taker_badge) = instantiate_bonded_escrow(&mut env, 3600);
+
+    perform_bonded_exchange(&mut env, component);
+
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 3600) * 1000);
+
+    reclaim_bond(&mut env, component, taker_badge).expect_commit_success();
+}
+
+
+// Subscription escrow
+
+const SUBSCRIPTION_PERIOD_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+// Account1 subscribes with 35 of the offered resource, account2 collects 10 per period
+fn instantiate_subscription_escrow(env: &mut TestEnvironment) -> (ComponentAddress, ResourceAddress) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account1, env.offered_resource, dec!("35"))
+        .take_all_from_worktop(env.offered_resource, "funds")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "SubscriptionEscrow",
+            "instantiate_subscription_escrow",
+            |lookup| (
+                EscrowResourceSpecifier::Fungible { resource_address: env.offered_resource, amount: dec!("10") },
+                SUBSCRIPTION_PERIOD_SECONDS,
+                env.requested_nft,
+                lookup.bucket("funds"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    let result = receipt.expect_commit(true);
+    (result.new_component_addresses()[0], result.new_resource_addresses()[0])
+}
+
+fn collect_subscription_manifest(env: &TestEnvironment, component: ComponentAddress) -> TransactionManifestV1 {
+    ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(env.account2, env.requested_nft, dec!("1"))
+        .pop_from_auth_zone("provider_badge")
+        .call_method_with_name_lookup(
+            component,
+            "collect",
+            |lookup| (
+                lookup.proof("provider_badge"),
+            )
+        )
+        .deposit_batch(env.account2)
+        .build()
+}
+
+fn collect_subscription(env: &mut TestEnvironment, component: ComponentAddress) {
+    let manifest = collect_subscription_manifest(env, component);
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_success();
+}
+
+#[test]
+fn test_subscription_collects_once_per_period() {
+    let mut env = setup();
+    let (component, _) = instantiate_subscription_escrow(&mut env);
+
+    // The first period can be collected right away, only once
+    collect_subscription(&mut env, component);
+    collect_subscription(&mut env, component);
+    assert_eq!(env.ledger.get_component_balance(env.account2, env.offered_resource), dec!("10"));
+
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + SUBSCRIPTION_PERIOD_SECONDS) * 1000);
+    collect_subscription(&mut env, component);
+    assert_eq!(env.ledger.get_component_balance(env.account2, env.offered_resource), dec!("20"));
+
+    // Only whole periods are collected, the remaining 5 can't cover the next one
+    env.ledger.advance_to_round_at_timestamp(Round::of(3), (now.seconds_since_unix_epoch + 5 * SUBSCRIPTION_PERIOD_SECONDS) * 1000);
+    collect_subscription(&mut env, component);
+    assert_eq!(env.ledger.get_component_balance(env.account2, env.offered_resource), dec!("30"));
+}
+
+#[test]
+fn test_cancel_subscription_returns_unspent_funds() {
+    let mut env = setup();
+    let (component, escrow_nft) = instantiate_subscription_escrow(&mut env);
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .take_all_from_worktop(escrow_nft, "escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            "cancel_subscription",
+            |lookup| (
+                lookup.bucket("escrow_nft"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+
+    // The current period stays with the provider, the rest is returned
+    assert_eq!(env.ledger.get_component_balance(env.account1, env.offered_resource), dec!("90"));
+    collect_subscription(&mut env, component);
+    assert_eq!(env.ledger.get_component_balance(env.account2, env.offered_resource), dec!("10"));
+}
+
+
+// Amending terms
+
+fn update_terms(env: &mut TestEnvironment, component: ComponentAddress, escrow_nft: ResourceAddress, requested: Vec<EscrowResourceSpecifier>) -> TransactionReceipt {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .pop_from_auth_zone("escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            "update_terms",
+            |lookup| (
+                lookup.proof("escrow_nft"),
+                requested,
+            )
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    )
+}
+
+#[test]
+fn test_update_terms_changes_requested_resource() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let requested_nft = env.requested_nft;
+    let offered_resource = env.offered_resource;
+    let (component, escrow_nft) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    update_terms(&mut env, component, escrow_nft,
+        vec![EscrowResourceSpecifier::NonFungible { resource_address: requested_nft, non_fungible_local_id: NonFungibleLocalId::integer(DEFAULT_ESCROW_ID) }]
+    ).expect_commit_success();
+
+    // The escrow now requires the non-fungible, and pays it out on withdrawal
+    perform_exchange(&mut env, component, requested_nft, NonFungibleLocalId::integer(DEFAULT_ESCROW_ID));
+    withdraw_resource(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_nft), dec!("1"));
+}
+
+#[test]
+fn test_exchange_pinned_to_old_terms_fails() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, escrow_nft) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    update_terms(&mut env, component, escrow_nft,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("20") }]
+    ).expect_commit_success();
+
+    for (terms_revision, amount, success) in [(0u64, dec!("10"), false), (0u64, dec!("20"), false), (1u64, dec!("20"), true)] {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(env.account2, requested_resource, amount)
+            .take_all_from_worktop(requested_resource, "requested_bucket")
+            .call_method_with_name_lookup(
+                component,
+                "exchange",
+                |lookup| (
+                    lookup.bucket("requested_bucket"),
+                    Some(terms_revision),
+                    None::<NonFungibleGlobalId>,
+                )
+            )
+            .deposit_batch(env.account2)
+            .build();
+        let receipt = env.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+        );
+        receipt.expect_commit(success);
+    }
+}
+
+
+// Adjusting the offered inventory
+
+#[test]
+fn test_deposit_more_offered_scales_requested_amount() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, escrow_nft) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .pop_from_auth_zone("escrow_nft")
+        .withdraw_from_account(env.account1, offered_resource, dec!("20"))
+        .take_all_from_worktop(offered_resource, "offered_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "deposit_more_offered",
+            |lookup| (
+                lookup.proof("escrow_nft"),
+                lookup.bucket("offered_bucket"),
+            )
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+
+    // 30 offered at the same price per unit now cost 30
+    perform_exchange(&mut env, component, requested_resource, dec!("30"));
+    assert_eq!(env.ledger.get_component_balance(env.account2, offered_resource), dec!("30"));
+}
+
+#[test]
+fn test_withdraw_offered_partial_keeps_escrow_open() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, escrow_nft) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .pop_from_auth_zone("escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            "withdraw_offered_partial",
+            |lookup| (
+                lookup.proof("escrow_nft"),
+                dec!("6"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+    assert_eq!(env.ledger.get_component_balance(env.account1, offered_resource), dec!("96"));
+
+    // 4 offered at the same price per unit now cost 4
+    perform_exchange(&mut env, component, requested_resource, dec!("4"));
+    withdraw_resource(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("4"));
+}
+
+#[test]
+fn test_deposit_more_offered_rejected_for_nft_request() {
+    let mut env = setup();
+    let requested_nft = env.requested_nft;
+    let offered_resource = env.offered_resource;
+    let (component, escrow_nft) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::NonFungible { resource_address: requested_nft, non_fungible_local_id: NonFungibleLocalId::integer(1) },
+        offered_resource,
+        dec!("10")
+    );
+
+    // The single NFT requested would buy 30 instead of 10
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .pop_from_auth_zone("escrow_nft")
+        .withdraw_from_account(env.account1, offered_resource, dec!("20"))
+        .take_all_from_worktop(offered_resource, "offered_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "deposit_more_offered",
+            |lookup| (
+                lookup.proof("escrow_nft"),
+                lookup.bucket("offered_bucket"),
+            )
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_failure();
+    assert_eq!(env.ledger.get_component_balance(env.account1, offered_resource), dec!("90"));
+}
+
+// Alternative requested resources
+
+#[test]
+fn test_exchange_with_any_alternative() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let requested_nft = env.requested_nft;
+    let offered_resource = env.offered_resource;
+    let alternatives = vec![
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        EscrowResourceSpecifier::NonFungible { resource_address: requested_nft, non_fungible_local_id: NonFungibleLocalId::integer(DEFAULT_ESCROW_ID) },
+    ];
+    let (component, escrow_nft) = instantiate_escrow_with_alternatives(&mut env, alternatives, offered_resource, dec!("10"));
+
+    // The taker pays with the second alternative
+    perform_exchange(&mut env, component, requested_nft, NonFungibleLocalId::integer(DEFAULT_ESCROW_ID));
+    withdraw_resource(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_nft), dec!("1"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("0"));
+}
+
+#[test]
+fn test_exchange_with_no_matching_alternative_fails() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let requested_nft = env.requested_nft;
+    let offered_resource = env.offered_resource;
+    let alternatives = vec![
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        EscrowResourceSpecifier::NonFungible { resource_address: requested_nft, non_fungible_local_id: NonFungibleLocalId::integer(DEFAULT_ESCROW_ID) },
+    ];
+    let (component, _) = instantiate_escrow_with_alternatives(&mut env, alternatives, offered_resource, dec!("10"));
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, requested_resource, dec!("5"))
+        .take_all_from_worktop(requested_resource, "requested_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "exchange",
+            |lookup| (
+                lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
+            )
+        )
+        .deposit_batch(env.account2)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    );
+    receipt.expect_commit_failure();
+}
+
+
+// Push settlement
+
+#[test]
+fn test_exchange_pushes_proceeds_to_settlement_account() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let options = EscrowOptions { settlement_account: Some(env.account1), ..Default::default() };
+    let (component, _) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+
+    // The instantiator got paid without calling withdraw_resource
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("10"));
+}
+
+#[test]
+fn test_rejected_push_settlement_is_kept_for_withdrawal() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let options = EscrowOptions { settlement_account: Some(env.account1), ..Default::default() };
+    let (component, escrow_nft) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    // The instantiator's account rejects deposits of resources it doesn't hold yet
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(
+            env.account1,
+            "set_default_deposit_rule",
+            manifest_args!(DefaultDepositRule::Reject),
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("0"));
+
+    // Withdrawing explicitly still works, as the owner of the account deposits to it
+    withdraw_resource(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("10"));
+}
+
+
+// Account locker
+
+fn get_account_locker(env: &mut TestEnvironment, component: ComponentAddress) -> ComponentAddress {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(component, "get_account_locker", manifest_args!())
+        .build();
+    let receipt = env.ledger.execute_manifest(manifest, vec![]);
+    receipt.expect_commit_success().output::<Option<ComponentAddress>>(1).unwrap()
+}
+
+fn claim_from_locker(env: &mut TestEnvironment, locker: ComponentAddress, account: ComponentAddress, public_key: Secp256k1PublicKey, resource: ResourceAddress, amount: Decimal) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(locker, "claim", manifest_args!(account, resource, amount))
+        .deposit_batch(account)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    );
+    receipt.expect_commit_success();
+}
+
+#[test]
+fn test_exchange_proceeds_claimable_from_account_locker() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let options = EscrowOptions { settlement_account: Some(env.account1), use_account_locker: true, ..Default::default() };
+    let (component, _) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("0"));
+
+    // The instantiator claims from the locker, without the escrow badge
+    let locker = get_account_locker(&mut env, component);
+    let (account1, public_key1) = (env.account1, env.public_key1);
+    claim_from_locker(&mut env, locker, account1, public_key1, requested_resource, dec!("10"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("10"));
+}
+
+#[test]
+fn test_cancel_refund_claimable_from_account_locker() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let options = EscrowOptions { settlement_account: Some(env.account1), use_account_locker: true, ..Default::default() };
+    let (component, escrow_nft) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account1, escrow_nft, [NonFungibleLocalId::integer(DEFAULT_ESCROW_ID)])
+        .take_all_from_worktop(escrow_nft, "escrow_nft")
+        .call_method_with_name_lookup(
+            component,
+            "cancel_escrow",
+            |lookup| (
+                lookup.bucket("escrow_nft"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+    assert_eq!(env.ledger.get_component_balance(env.account1, offered_resource), dec!("90"));
+
+    let locker = get_account_locker(&mut env, component);
+    let (account1, public_key1) = (env.account1, env.public_key1);
+    claim_from_locker(&mut env, locker, account1, public_key1, offered_resource, dec!("10"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, offered_resource), dec!("100"));
+}
+
+#[test]
+fn test_taker_claims_offered_from_account_locker() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let options = EscrowOptions { settlement_account: Some(env.account1), use_account_locker: true, ..Default::default() };
+    let (component, _) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, requested_resource, dec!("10"))
+        .take_all_from_worktop(requested_resource, "requested_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "exchange_via_locker",
+            |lookup| (
+                lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
+                env.account2,
+            )
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_success();
+    assert_eq!(env.ledger.get_component_balance(env.account2, offered_resource), dec!("0"));
+
+    // Both parties claim what they are owed from the locker
+    let locker = get_account_locker(&mut env, component);
+    let (account1, public_key1) = (env.account1, env.public_key1);
+    let (account2, public_key2) = (env.account2, env.public_key2);
+    claim_from_locker(&mut env, locker, account2, public_key2, offered_resource, dec!("10"));
+    claim_from_locker(&mut env, locker, account1, public_key1, requested_resource, dec!("10"));
+    assert_eq!(env.ledger.get_component_balance(env.account2, offered_resource), dec!("10"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("10"));
+}
+
+#[test]
+fn test_exchange_via_locker_requires_account_locker() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, _) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, requested_resource, dec!("10"))
+        .take_all_from_worktop(requested_resource, "requested_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "exchange_via_locker",
+            |lookup| (
+                lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
+                env.account2,
+            )
+        )
+        .deposit_batch(env.account2)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_failure();
+}
+
+// Completion callback
+
+#[test]
+fn test_exchange_notifies_callback() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_function(env.package_address, "MockEscrowCallback", "instantiate_mock_escrow_callback", manifest_args!())
+        .build();
+    let receipt = env.ledger.execute_manifest(manifest, vec![]);
+    let callback = receipt.expect_commit_success().new_component_addresses()[0];
+
+    let options = EscrowOptions {
+        callback: Some(EscrowCallback { component: callback, method: MOCK_ESCROW_CALLBACK_METHOD.to_string() }),
+        ..Default::default()
+    };
+    let (component, _) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(callback, "get_fills", manifest_args!())
+        .build();
+    let receipt = env.ledger.execute_manifest(manifest, vec![]);
+    let fills: Vec<EscrowFill> = receipt.expect_commit_success().output(1);
+
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].escrow, GlobalAddress::from(component));
+    assert_eq!(fills[0].paid.get_resource_address(), requested_resource);
+    assert_eq!(fills[0].offered.get_resource_address(), offered_resource);
+    assert_eq!(fills[0].terms_revision, 0);
+}
+
+
+// Taker receipt
+
+fn get_taker_receipt(env: &mut TestEnvironment, component: ComponentAddress) -> Option<ResourceAddress> {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(component, "get_taker_receipt", manifest_args!())
+        .build();
+    let receipt = env.ledger.execute_manifest(manifest, vec![]);
+    receipt.expect_commit_success().output(1)
+}
+
+#[test]
+fn test_exchange_mints_taker_receipt() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let options = EscrowOptions { mint_taker_receipt: true, ..Default::default() };
+    let (component, _) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("20") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    perform_exchange(&mut env, component, requested_resource, dec!("20"));
+
+    let taker_receipt = get_taker_receipt(&mut env, component).unwrap();
+    assert_eq!(env.ledger.get_component_balance(env.account2, taker_receipt), dec!("1"));
+
+    let vault = env.ledger.get_component_vaults(env.account2, taker_receipt)[0];
+    let (_, mut ids) = env.ledger.inspect_non_fungible_vault(vault).unwrap();
+    let receipt_id = ids.next().unwrap();
+    let data: TakerReceipt = env.ledger.get_non_fungible_data(taker_receipt, receipt_id);
+    assert_eq!(data.escrow, GlobalAddress::from(component));
+    assert_eq!(data.offered.get_resource_address(), offered_resource);
+    assert_eq!(data.paid.get_resource_address(), requested_resource);
+    assert_eq!(data.price, Some(dec!("2")));
+}
+
+#[test]
+fn test_exchange_without_taker_receipt() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, _) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+
+    assert_eq!(get_taker_receipt(&mut env, component), None);
+}
+
+
+// Referral fees
+
+fn exchange_with_referrer(env: &mut TestEnvironment, component: ComponentAddress, referrer: NonFungibleGlobalId) {
+    let requested_resource = env.requested_resource;
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, requested_resource, dec!("10"))
+        .take_all_from_worktop(requested_resource, "requested_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "exchange",
+            |lookup| (
+                lookup.bucket("requested_bucket"),
+                None::<u64>,
+                Some(referrer),
+            )
+        )
+        .deposit_batch(env.account2)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_success();
+}
+
+#[test]
+fn test_referrer_claims_fee_on_filled_escrow() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+
+    // The frontend routing the taker holds a badge of its own, listed in the fee schedule
+    let (referrer_key, _, referrer_account) = env.ledger.new_allocated_account();
+    let referrer_badge = env.ledger.create_non_fungible_resource(referrer_account);
+    let referrer_id = NonFungibleLocalId::integer(1);
+    let referrer = NonFungibleGlobalId::new(referrer_badge, referrer_id.clone());
+
+    let options = EscrowOptions { referrers: indexmap!(referrer.clone() => dec!("0.1")), ..Default::default() };
+    let (component, escrow_nft) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    exchange_with_referrer(&mut env, component, referrer);
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(referrer_account, referrer_badge, [referrer_id])
+        .pop_from_auth_zone("referrer_badge")
+        .call_method_with_name_lookup(
+            component,
+            "claim_referral_fees",
+            |lookup| (
+                lookup.proof("referrer_badge"),
+                requested_resource,
+            )
+        )
+        .deposit_batch(referrer_account)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&referrer_key)],
+    ).expect_commit_success();
+    assert_eq!(env.ledger.get_component_balance(referrer_account, requested_resource), dec!("1"));
+
+    // The instantiator gets the rest
+    withdraw_resource(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("9"));
+}
+
+#[test]
+fn test_exchange_without_referrer_pays_no_fee() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let referrer_badge = env.ledger.create_non_fungible_resource(env.account1);
+    let referrer = NonFungibleGlobalId::new(referrer_badge, NonFungibleLocalId::integer(1));
+    let options = EscrowOptions { referrers: indexmap!(referrer => dec!("0.1")), ..Default::default() };
+    let (component, escrow_nft) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+    withdraw_resource(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("10"));
+}
+
+#[test]
+fn test_unlisted_referrer_earns_no_fee() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let referrer_badge = env.ledger.create_non_fungible_resource(env.account1);
+    let referrer = NonFungibleGlobalId::new(referrer_badge, NonFungibleLocalId::integer(1));
+    let options = EscrowOptions { referrers: indexmap!(referrer => dec!("0.1")), ..Default::default() };
+    let (component, escrow_nft) = instantiate_escrow_with_options(&mut env,
+        vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }],
+        offered_resource,
+        dec!("10"),
+        options
+    );
+
+    // The taker refers themselves with a badge of their own to get a discount
+    let taker_badge = env.ledger.create_non_fungible_resource(env.account2);
+    exchange_with_referrer(&mut env, component, NonFungibleGlobalId::new(taker_badge, NonFungibleLocalId::integer(1)));
+    withdraw_resource(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("10"));
+}
+
+
+// Creation deposit
+
+fn instantiate_escrow_with_deposit(env: &mut TestEnvironment, deposit: Decimal) -> (ComponentAddress, ResourceAddress) {
+    let requested = vec![EscrowResourceSpecifier::Fungible { resource_address: env.requested_resource, amount: dec!("10") }];
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account1, env.offered_resource, dec!("10"))
+        .take_all_from_worktop(env.offered_resource, "offered_bucket")
+        .withdraw_from_account(env.account1, XRD, deposit)
+        .take_all_from_worktop(XRD, "deposit_bucket")
+        .call_function_with_name_lookup(
+            env.package_address,
+            "Escrow",
+            "instantiate_escrow",
+            |lookup| (
+                requested,
+                lookup.bucket("offered_bucket"),
+                EscrowOptions::default(),
+                Some(lookup.bucket("deposit_bucket")),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    let result = receipt.expect_commit(true);
+    (result.new_component_addresses()[0], result.new_resource_addresses()[0])
+}
+
+fn cancel_escrow(env: &mut TestEnvironment, component: ComponentAddress, escrow_nft: ResourceAddress) {
+    let manifest = manifests::cancel_escrow(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        env.account1,
+        component,
+        escrow_nft,
+        NonFungibleLocalId::integer(DEFAULT_ESCROW_ID),
+        None,
+    )
+    .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    receipt.expect_commit_success();
+}
+
+#[test]
+fn test_creation_deposit_refunded_on_withdraw() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let xrd_before = env.ledger.get_component_balance(env.account1, XRD);
+    let (component, escrow_nft) = instantiate_escrow_with_deposit(&mut env, dec!("100"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), xrd_before - dec!("100"));
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+    withdraw_resource(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), xrd_before);
+}
+
+#[test]
+fn test_early_cancel_forfeits_half_of_creation_deposit() {
+    let mut env = setup();
+    let xrd_before = env.ledger.get_component_balance(env.account1, XRD);
+    let (component, escrow_nft) = instantiate_escrow_with_deposit(&mut env, dec!("100"));
+
+    cancel_escrow(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), xrd_before - dec!("50"));
+}
+
+#[test]
+fn test_governor_admin_withdraws_forfeited_deposits() {
+    let mut env = setup();
+    let (component, escrow_nft) = instantiate_escrow_with_deposit(&mut env, dec!("100"));
+    cancel_escrow(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.governor, XRD), dec!("50"));
+
+    // The forfeit stays with the governor until its admin withdraws it
+    let xrd_before = env.ledger.get_component_balance(env.account1, XRD);
+    let manifest = ManifestBuilder::new()
+        .lock_fee(env.account2, dec!("10"))
+        .create_proof_from_account_of_amount(env.account1, env.governor_admin_badge, dec!("1"))
+        .pop_from_auth_zone("admin_badge")
+        .call_method_with_name_lookup(
+            env.governor,
+            "withdraw_forfeited_deposits",
+            |lookup| (
+                lookup.proof("admin_badge"),
+            )
+        )
+        .deposit_batch(env.account1)
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![
+            NonFungibleGlobalId::from_public_key(&env.public_key1),
+            NonFungibleGlobalId::from_public_key(&env.public_key2),
+        ],
+    ).expect_commit_success();
+
+    assert_eq!(env.ledger.get_component_balance(env.governor, XRD), dec!("0"));
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), xrd_before + dec!("50"));
+}
+
+#[test]
+fn test_cancel_after_minimum_open_period_refunds_creation_deposit() {
+    let mut env = setup();
+    let xrd_before = env.ledger.get_component_balance(env.account1, XRD);
+    let (component, escrow_nft) = instantiate_escrow_with_deposit(&mut env, dec!("100"));
+
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    env.ledger.advance_to_round_at_timestamp(Round::of(2), (now.seconds_since_unix_epoch + 24 * 60 * 60) * 1000);
+    cancel_escrow(&mut env, component, escrow_nft);
+
+    assert_eq!(env.ledger.get_component_balance(env.account1, XRD), xrd_before);
+}
+
+
+// Emergency pause
+
+fn call_governor(env: &mut TestEnvironment, method: &str) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(env.account1, env.governor_admin_badge, dec!("1"))
+        .pop_from_auth_zone("admin_badge")
+        .call_method_with_name_lookup(
+            env.governor,
+            method,
+            |lookup| (
+                lookup.proof("admin_badge"),
+            )
+        )
+        .build();
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    ).expect_commit_success();
+}
+
+#[test]
+fn test_paused_escrow_blocks_exchange_but_allows_cancel() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, escrow_nft) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    call_governor(&mut env, "pause");
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account2, requested_resource, dec!("10"))
+        .take_all_from_worktop(requested_resource, "requested_bucket")
+        .call_method_with_name_lookup(
+            component,
+            "exchange",
+            |lookup| (
+                lookup.bucket("requested_bucket"),
+                None::<u64>,
+                None::<NonFungibleGlobalId>,
+            )
+        )
+        .deposit_batch(env.account2)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    );
+    receipt.expect_commit_failure();
+
+    // Users can still exit while paused
+    cancel_escrow(&mut env, component, escrow_nft);
+    assert_eq!(env.ledger.get_component_balance(env.account1, offered_resource), dec!("100"));
+}
+
+#[test]
+fn test_paused_governor_blocks_instantiate_escrow() {
+    let mut env = setup();
+    let offered = EscrowResourceSpecifier::Fungible { resource_address: env.offered_resource, amount: dec!("10") };
+    let requested = EscrowResourceSpecifier::Fungible { resource_address: env.requested_resource, amount: dec!("10") };
+
+    call_governor(&mut env, "pause");
+
+    let manifest = manifests::instantiate_escrow(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        env.account1,
+        env.package_address,
+        &offered,
+        vec![requested],
+        EscrowOptions::default(),
+    )
+    .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    receipt.expect_commit_failure();
+}
+
+#[test]
+fn test_unpaused_escrow_exchanges_again() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, _) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    call_governor(&mut env, "pause");
+    call_governor(&mut env, "unpause");
+
+    perform_exchange(&mut env, component, requested_resource, dec!("10"));
+}
+
+#[test]
+fn test_paused_english_auction_blocks_bids() {
+    let mut env = setup();
+    let (component, _, _) = instantiate_english_auction(&mut env, dec!("100"), dec!("10"));
+
+    call_governor(&mut env, "pause");
+
+    let (account2, public_key2) = (env.account2, env.public_key2);
+    place_bid(&mut env, component, account2, public_key2, dec!("100")).expect_commit_failure();
+}
+
+#[test]
+fn test_paused_sealed_bid_auction_blocks_commits() {
+    let mut env = setup();
+    let now = env.ledger.get_current_time(TimePrecision::Second);
+    let (component, _, _) = instantiate_sealed_bid_auction(&mut env, dec!("100"), now.add_seconds(60).unwrap(), now.add_seconds(120).unwrap());
+
+    call_governor(&mut env, "pause");
+
+    let manifest = commit_sealed_bid_manifest(component, env.account2, dec!("200"), sealed_bid_commitment(dec!("120"), "salt2"));
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_failure();
+}
+
+#[test]
+fn test_paused_oracle_escrow_blocks_exchange() {
+    let mut env = setup();
+    let oracle = instantiate_mock_price_oracle(&mut env);
+    set_oracle_price(&mut env, oracle, dec!("5"));
+    let component = instantiate_oracle_escrow(&mut env, oracle);
+
+    call_governor(&mut env, "pause");
+
+    perform_oracle_exchange(&mut env, component, dec!("50")).expect_commit_failure();
+}
+
+#[test]
+fn test_paused_bonded_escrow_blocks_exchange() {
+    let mut env = setup();
+    let (component, _, _) = instantiate_bonded_escrow(&mut env, 60);
+
+    call_governor(&mut env, "pause");
+
+    let manifest = bonded_exchange_manifest(&env, component);
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_failure();
+}
+
+#[test]
+fn test_paused_subscription_escrow_blocks_collect() {
+    let mut env = setup();
+    let (component, _) = instantiate_subscription_escrow(&mut env);
+
+    call_governor(&mut env, "pause");
+
+    let manifest = collect_subscription_manifest(&env, component);
+    env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    ).expect_commit_failure();
+}
+
+#[test]
+fn test_escrow_requires_package_governor() {
+    let mut ledger = LedgerSimulatorBuilder::new().build();
+    let (public_key, _private_key, account) = ledger.new_allocated_account();
+    let package_address = ledger.compile_and_publish(this_package!());
+    let offered = EscrowResourceSpecifier::Fungible { resource_address: XRD, amount: dec!("10") };
+    let requested = EscrowResourceSpecifier::Fungible { resource_address: XRD, amount: dec!("20") };
+
+    // Nobody named a governor in the package metadata, so the escrow can't be paused
+    let manifest = manifests::instantiate_escrow(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        account,
+        package_address,
+        &offered,
+        vec![requested],
+        EscrowOptions::default(),
+    )
+    .build();
+    let receipt = ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    );
+    receipt.expect_commit_failure();
+}
+
+
+// Worktop guarantees
+
+#[test]
+fn test_exchange_guarantee_enforced() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+
+    for (guaranteed_amount, success) in [(dec!("11"), false), (dec!("10"), true)] {
+        let (component, _) = instantiate_escrow(&mut env,
+            EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+            offered_resource,
+            dec!("10")
+        );
+        let guarantee = EscrowResourceSpecifier::Fungible { resource_address: offered_resource, amount: guaranteed_amount };
+
+        let manifest = manifests::exchange_fungible(
+            ManifestBuilder::new().lock_fee_from_faucet(),
+            env.account2,
+            component,
+            requested_resource,
+            dec!("10"),
+            None,
+            Some(&guarantee),
+        )
+        .build();
+        let receipt = env.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+        );
+        receipt.expect_commit(success);
+    }
+}
+
+#[test]
+fn test_withdraw_guarantee_enforced() {
+    let mut env = setup();
+    let requested_nft = env.requested_nft;
+    let offered_resource = env.offered_resource;
+    let requested = EscrowResourceSpecifier::NonFungible { resource_address: requested_nft, non_fungible_local_id: NonFungibleLocalId::integer(DEFAULT_ESCROW_ID) };
+    let (component, escrow_nft) = instantiate_escrow(&mut env, requested.clone(), offered_resource, dec!("10"));
+
+    perform_exchange(&mut env, component, requested_nft, NonFungibleLocalId::integer(DEFAULT_ESCROW_ID));
+
+    let manifest = manifests::withdraw_resource(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        env.account1,
+        component,
+        escrow_nft,
+        NonFungibleLocalId::integer(DEFAULT_ESCROW_ID),
+        Some(&requested),
+    )
+    .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+    );
+    receipt.expect_commit_success();
+}
+
+#[test]
+fn test_withdraw_guarantee_accounts_for_referral_fee() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let referrer_badge = env.ledger.create_non_fungible_resource(env.account1);
+    let referrer = NonFungibleGlobalId::new(referrer_badge, NonFungibleLocalId::integer(1));
+    let requested = vec![EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") }];
+    let options = EscrowOptions { referrers: indexmap!(referrer.clone() => dec!("0.1")), ..Default::default() };
+    let (component, escrow_nft) = instantiate_escrow_with_options(&mut env, requested.clone(), offered_resource, dec!("10"), options);
+    exchange_with_referrer(&mut env, component, referrer);
+
+    // The full requested amount can't be guaranteed once the referrer took its fee
+    let guarantee = manifests::withdraw_guarantee(&requested, dec!("0.1")).unwrap();
+    for (guarantee, success) in [(requested[0].clone(), false), (guarantee, true)] {
+        let manifest = manifests::withdraw_resource(
+            ManifestBuilder::new().lock_fee_from_faucet(),
+            env.account1,
+            component,
+            escrow_nft,
+            NonFungibleLocalId::integer(DEFAULT_ESCROW_ID),
+            Some(&guarantee),
+        )
+        .build();
+        let receipt = env.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&env.public_key1)],
+        );
+        receipt.expect_commit(success);
+    }
+    assert_eq!(env.ledger.get_component_balance(env.account1, requested_resource), dec!("9"));
+}
+
+// Fee strategies
+
+#[test]
+fn test_exchange_fee_locked_from_account() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, _) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+    let xrd_before = env.ledger.get_component_balance(env.account2, XRD);
+
+    let builder = manifests::lock_fee(ManifestBuilder::new(), manifests::FeeStrategy::Account, env.account2, dec!("10"));
+    let manifest = manifests::exchange_fungible(builder, env.account2, component, requested_resource, dec!("10"), None, None)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    );
+    receipt.expect_commit_success();
+
+    let xrd_after = env.ledger.get_component_balance(env.account2, XRD);
+    assert!(xrd_after < xrd_before);
+    assert_eq!(env.ledger.get_component_balance(env.account2, offered_resource), dec!("10"));
+}
+
+#[test]
+fn test_exchange_with_contingent_fee_committed() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, _) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+    let xrd_before = env.ledger.get_component_balance(env.account2, XRD);
+
+    let builder = manifests::lock_fee(ManifestBuilder::new(), manifests::FeeStrategy::Contingent, env.account2, dec!("10"));
+    let manifest = manifests::exchange_fungible(builder, env.account2, component, requested_resource, dec!("10"), None, None)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    );
+    receipt.expect_commit_success();
+
+    assert!(env.ledger.get_component_balance(env.account2, XRD) < xrd_before);
+    assert_eq!(env.ledger.get_component_balance(env.account2, offered_resource), dec!("10"));
+}
+
+#[test]
+fn test_manifest_without_fee_rejected() {
+    let mut env = setup();
+    let requested_resource = env.requested_resource;
+    let offered_resource = env.offered_resource;
+    let (component, _) = instantiate_escrow(&mut env,
+        EscrowResourceSpecifier::Fungible { resource_address: requested_resource, amount: dec!("10") },
+        offered_resource,
+        dec!("10")
+    );
+
+    let builder = manifests::lock_fee(ManifestBuilder::new(), manifests::FeeStrategy::None, env.account2, dec!("10"));
+    let manifest = manifests::exchange_fungible(builder, env.account2, component, requested_resource, dec!("10"), None, None)
+        .build();
+    let receipt = env.ledger.execute_manifest(
+        manifest,
+        vec![NonFungibleGlobalId::from_public_key(&env.public_key2)],
+    );
+    receipt.expect_rejection();
+}
+
+// Manifest formats
+
+// A new directory under the system temp directory, distinct across test processes and runs
+fn unique_temp_dir(name: &str) -> std::path::PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let directory = std::env::temp_dir().join(format!("escrow_test_{}_{}_{}", name, std::process::id(), nanos));
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[test]
+fn test_write_manifest_formats() {
+    let directory = unique_temp_dir("write_manifest_formats");
+    let network = NetworkDefinition::simulator();
+    let expected = ManifestBuilder::new().lock_fee_from_faucet().build();
+
+    manifests::write_manifest(
+        ManifestBuilder::new().lock_fee_from_faucet(),
+        &directory,
+        "lock_fee",
+        &network,
+        &[manifests::ManifestFormat::Rtm, manifests::ManifestFormat::Compiled, manifests::ManifestFormat::Json],
+    );
+
+    let rtm = std::fs::read_to_string(directory.join("lock_fee.rtm")).unwrap();
+    assert!(rtm.contains("\"lock_fee\""));
+    let compiled = std::fs::read(directory.join("lock_fee.bin")).unwrap();
+    assert_eq!(manifest_decode::<radix_transactions::model::TransactionManifestV1>(&compiled).unwrap(), expected);
+    let json = std::fs::read_to_string(directory.join("lock_fee.json")).unwrap();
+    let request: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let transaction_manifest = request["transactionManifest"].as_str().unwrap();
+    assert_eq!(transaction_manifest, rtm);
+    assert_eq!(
+        radix_transactions::manifest::compile(transaction_manifest, &network, radix_transactions::manifest::BlobProvider::new()).unwrap(),
+        expected
+    );
+    assert_eq!(request["version"], 1);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "The wallet locks the fee of a json manifest, pass --fee none")]
+fn test_cli_rejects_fee_lock_in_json_manifest() {
+    let profiles = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("network_profiles.toml");
+    let cli = Cli::parse_from(["escrow", "--profiles", profiles.to_str().unwrap(), "--format", "json", "cancel-escrow"]);
+    let profile = NetworkProfile::load(&cli.profiles, &cli.profile);
+
+    generate(&cli, &profile);
+}
+
+#[test]
+fn test_cli_writes_json_manifest_without_fee() {
+    let directory = unique_temp_dir("cli_json_manifest");
+    let profiles = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("network_profiles.toml");
+    let cli = Cli::parse_from(["escrow", "--profiles", profiles.to_str().unwrap(), "--format", "json", "--fee", "none", "cancel-escrow"]);
+    let profile = NetworkProfile::load(&cli.profiles, &cli.profile);
+
+    let (builder, name) = generate(&cli, &profile);
+    manifests::write_manifest(builder, &directory, &name, &profile.network, &cli.formats);
+
+    let json = std::fs::read_to_string(directory.join("cancel_escrow.json")).unwrap();
+    let request: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(!request["transactionManifest"].as_str().unwrap().contains("lock_fee"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+// Committed manifests
+
+// The `escrow` arguments of the manifests committed in `transaction_manifest/`, generated with the
+// stokenet profile
+fn committed_manifest_args() -> Vec<Vec<&'static str>> {
+    vec![
+        vec!["instantiate-escrow", "--offered", "resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc:1", "--requested", "resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc:2"],
+        vec!["instantiate-escrow", "--offered", "resource_tdx_2_1ntxn2zuu59fhetlg6xcvm0zpe3naa9pcwt7mpwc6hhkm9qq9myddrs:#0#", "--requested", "resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc:5", "--name", "instantiate_escrow_nft"],
+        vec!["instantiate-escrow", "--offered", "resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc:5", "--requested", "resource_tdx_2_1ntxn2zuu59fhetlg6xcvm0zpe3naa9pcwt7mpwc6hhkm9qq9myddrs:#0#", "--name", "instantiate_escrow_request_nft"],
+        vec!["exchange", "--component", "component_tdx_2_1cq95llglldhwa8gtmq7kdzqr6mvn02y42qj8p7qptnl2gvfrwswvww", "--paid", "resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc:5"],
+        vec!["exchange", "--component", "component_tdx_2_1cpeer9jteykrff5hng6uwv4zruud9s4f329k3uv3y5lyjds2wjkxt9", "--paid", "resource_tdx_2_1ntxn2zuu59fhetlg6xcvm0zpe3naa9pcwt7mpwc6hhkm9qq9myddrs:#0#", "--name", "exchange_nft"],
+        vec!["withdraw-resource", "--component", "component_tdx_2_1cpeer9jteykrff5hng6uwv4zruud9s4f329k3uv3y5lyjds2wjkxt9", "--badge", "resource_tdx_2_1nt693t58qracladxxz7h2q8rdvjrwsnye7w7n7hh3rl2ypn7eaczgd"],
+        vec!["cancel-escrow"],
+    ]
+}
+
+// Run with `UPDATE_MANIFESTS=1` to regenerate `transaction_manifest/` instead of checking it
+#[test]
+fn test_committed_manifests_up_to_date() {
+    let package_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let committed = package_dir.join("transaction_manifest");
+    let profiles = package_dir.join("network_profiles.toml");
+    let update = std::env::var("UPDATE_MANIFESTS").is_ok();
+    let directory = if update { committed.clone() } else { unique_temp_dir("committed_manifests") };
+
+    let mut generated_names = Vec::new();
+    for args in committed_manifest_args() {
+        // Built as `escrow` builds them, with the defaults of the profile
+        let cli = Cli::parse_from(["escrow", "--profiles", profiles.to_str().unwrap()].into_iter().chain(args));
+        let profile = NetworkProfile::load(&cli.profiles, &cli.profile);
+        let (builder, name) = generate(&cli, &profile);
+        manifests::write_manifest(builder, &directory, &name, &profile.network, &cli.formats);
+        generated_names.push(name);
+    }
+    if update {
+        return;
+    }
+
+    let mut committed_names: Vec<String> = std::fs::read_dir(&committed)
+        .unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+        .filter_map(|file_name| file_name.strip_suffix(".rtm").map(str::to_string))
+        .collect();
+    committed_names.sort();
+    generated_names.sort();
+    assert_eq!(committed_names, generated_names, "transaction_manifest/ holds manifests the tests do not generate");
+
+    for name in generated_names {
+        let file_name = format!("{}.rtm", name);
+        assert_eq!(
+            std::fs::read_to_string(directory.join(&file_name)).unwrap(),
+            std::fs::read_to_string(committed.join(&file_name)).unwrap(),
+            "transaction_manifest/{} drifted from what `escrow` generates, regenerate it with UPDATE_MANIFESTS=1",
+            file_name
+        );
+    }
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_cli_passes_escrow_options_and_referrer() {
+    let directory = unique_temp_dir("cli_escrow_options");
+    let profiles = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("network_profiles.toml");
+    let xrd = "resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc";
+    let referrer = "resource_tdx_2_1nt693t58qracladxxz7h2q8rdvjrwsnye7w7n7hh3rl2ypn7eaczgd:#1#";
+    let instantiate_args = vec![
+        "instantiate-escrow".to_string(), "--offered".to_string(), format!("{}:1", xrd), "--requested".to_string(), format!("{}:2", xrd),
+        "--settlement-account".to_string(), "account_tdx_2_12xh47xjynaa57nf4wp9xkvcxaasdle0d9w4gglxuce789dz3tffkzx".to_string(),
+        "--callback".to_string(), "component_tdx_2_1crn3hpvu29nuvg74a5zhgr87kkqvktrn39k0429c2n4fmrc90vuv24:on_escrow_filled".to_string(),
+        "--mint-taker-receipt".to_string(), "--referrer".to_string(), format!("{}:0.01", referrer),
+        "--creation-deposit".to_string(), "10".to_string(),
+    ];
+    let exchange_args = vec![
+        "exchange".to_string(), "--paid".to_string(), format!("{}:2", xrd), "--referrer".to_string(), referrer.to_string(),
+    ];
+
+    for args in [instantiate_args, exchange_args] {
+        let cli = Cli::parse_from(["escrow".to_string(), "--profiles".to_string(), profiles.to_str().unwrap().to_string()].into_iter().chain(args));
+        let profile = NetworkProfile::load(&cli.profiles, &cli.profile);
+        let (builder, name) = generate(&cli, &profile);
+        manifests::write_manifest(builder, &directory, &name, &profile.network, &cli.formats);
+    }
+
+    let instantiate = std::fs::read_to_string(directory.join("instantiate_escrow.rtm")).unwrap();
+    assert!(instantiate.contains("Bucket(\"creation_deposit\")"));
+    assert!(instantiate.contains("\"on_escrow_filled\""));
+    assert!(instantiate.contains(&format!("NonFungibleGlobalId(\"{}\")", referrer)));
+    let exchange = std::fs::read_to_string(directory.join("exchange.rtm")).unwrap();
+    assert!(exchange.contains(&format!("NonFungibleGlobalId(\"{}\")", referrer)));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+// Manifest explainer
+
+#[test]
+fn test_explain_committed_instantiate_escrow() {
+    let manifest = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("transaction_manifest/instantiate_escrow_request_nft.rtm")
+    ).unwrap();
+
+    let explanation = explainer::explain_rtm(&manifest, &NetworkDefinition::stokenet());
+
+    assert_eq!(explanation.len(), 5);
+    assert_eq!(explanation[0], "Locks a fee of up to 5000 XRD from the faucet");
+    assert_eq!(
+        explanation[3],
+        "Instantiates an escrow of package package_tdx_2_1p5emmw82zqhx0ufhplc08u4me8g32llvxxerp9jk2fnmdl2e6ma32y \
+        offering 5 resource_tdx_2_1tknxxxxxxxxxradxrdxxxxxxxxx009923554798xxxxxxxxxtfd2jc in exchange for any one of: \
+        resource_tdx_2_1ntxn2zuu59fhetlg6xcvm0zpe3naa9pcwt7mpwc6hhkm9qq9myddrs #0#"
+    );
+}
+
+#[test]
+fn test_explain_exchange_with_guarantee() {
+    let env = setup();
+    let paid = EscrowResourceSpecifier::Fungible { resource_address: env.requested_resource, amount: dec!("10") };
+    let guarantee = EscrowResourceSpecifier::Fungible { resource_address: env.offered_resource, amount: dec!("10") };
+    let manifest = manifests::exchange(
+        manifests::lock_fee(ManifestBuilder::new(), manifests::FeeStrategy::Faucet, env.account2, dec!("10")),
+        env.account2,
+        env.account1,
+        &paid,
+        Some(3),
+        Some(&guarantee),
+    )
+    .build();
+    let network = NetworkDefinition::simulator();
+    let encoder = AddressBech32Encoder::new(&network);
+    let address = |address: GlobalAddress| encoder.encode(address.as_node_id().as_bytes()).unwrap();
+
+    let explanation = explainer::explain(&manifest, &network);
+
+    assert_eq!(explanation[0], "Locks a fee of up to 5000 XRD from the faucet");
+    assert_eq!(
+        explanation[3],
+        format!(
+            "Pays 10 {} to the escrow {} for its offered resource, failing unless its terms are at revision 3",
+            address(env.requested_resource.into()),
+            address(env.account1.into())
+        )
+    );
+    assert_eq!(
+        explanation[4],
+        format!("Fails unless the worktop holds at least 10 {}", address(env.offered_resource.into()))
+    );
+}
+
+#[test]
+fn test_explain_oracle_exchange_as_generic_call() {
+    let mut env = setup();
+    let oracle = instantiate_mock_price_oracle(&mut env);
+    set_oracle_price(&mut env, oracle, dec!("5"));
+    let component = instantiate_oracle_escrow(&mut env, oracle);
+    let manifest = oracle_exchange_manifest(&env, component, dec!("50"));
+    let network = NetworkDefinition::simulator();
+    let encoder = AddressBech32Encoder::new(&network);
+    let address = |address: GlobalAddress| encoder.encode(address.as_node_id().as_bytes()).unwrap();
+
+    let explanation = explainer::explain(&manifest, &network);
+
+    // Its `exchange` takes only the payment, unlike the `Escrow` one
+    assert_eq!(explanation.len(), 5);
+    assert_eq!(explanation[3], format!("Calls exchange on {}", address(component.into())));
+    assert_eq!(
+        explanation[4],
+        format!("Deposits everything left on the worktop into {}", address(env.account2.into()))
+    );
+}
+
+// Dry runs
+
+fn dry_run_committed(name: &str) -> dry_run::DryRunReport {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let manifest = std::fs::read_to_string(directory.join("transaction_manifest").join(format!("{}.rtm", name))).unwrap();
+    let fixture = dry_run::Fixture::load(directory.join("dry_run_fixture.toml").to_str().unwrap());
+    dry_run::dry_run(&manifest, &NetworkDefinition::stokenet(), &fixture)
+}
+
+#[test]
+fn test_dry_run_exchange() {
+    let report = dry_run_committed("exchange");
+
+    assert!(report.success, "{}", report.outcome);
+    assert!(!report.faucet_fee_added);
+    assert!(report.fees.iter().any(|(fee, amount)| *fee == "Total" && *amount > Decimal::ZERO));
+    let account = "account_tdx_2_12xh47xjynaa57nf4wp9xkvcxaasdle0d9w4gglxuce789dz3tffkzx".to_string();
+    assert_eq!(report.balance_changes, vec![
+        (account.clone(), "XRD".to_string(), dec!("-5")),
+        (account, "resource_tdx_2_1ntxn2zuu59fhetlg6xcvm0zpe3naa9pcwt7mpwc6hhkm9qq9myddrs".to_string(), dec!("1")),
+    ]);
+}
+
+#[test]
+fn test_dry_run_withdraw_before_exchange_fails() {
+    let report = dry_run_committed("withdraw_resource");
+
+    assert!(!report.success);
+    assert!(report.balance_changes.is_empty());
+}
+
+// Resim script
+
+#[test]
+fn test_resim_demo_script_manifests() {
+    let script = resim::demo_script(dec!("100"), dec!("50"));
+
+    assert!(script.starts_with("#!/usr/bin/env bash\n"));
+    assert!(script.contains("Address(\"${ACCOUNT1}\")"));
+    assert!(script.contains("Address(\"${PACKAGE}\")"));
+    assert!(script.contains("Address(\"${GOVERNOR}\")"));
+    assert!(script.contains("\"exchange\""));
+    assert!(script.contains("ASSERT_WORKTOP_CONTAINS\n    Address(\"${REQUESTED}\")\n    Decimal(\"50\")"));
+    // Only the faucet keeps its address, every other address comes from resim
+    let faucet = AddressBech32Encoder::new(&NetworkDefinition::simulator())
+        .encode(FAUCET.as_node_id().as_bytes())
+        .unwrap();
+    assert_eq!(script.matches("_sim1").count(), script.matches(faucet.as_str()).count());
+}
@@ -0,0 +1,71 @@
+use clap::Parser;
+use escrow_exercise_boilerplate::cli::{generate, Cli, Command};
+use escrow_exercise_boilerplate::profiles::NetworkProfile;
+use escrow_exercise_boilerplate::{explainer, manifests, resim};
+use scrypto::prelude::*;
+use std::path::Path;
+
+fn main() {
+    let cli = Cli::parse();
+    let profile = NetworkProfile::load(&cli.profiles, &cli.profile);
+    let network = profile.network.clone();
+
+    if let Command::Explain { manifest } = &cli.command {
+        let manifest = std::fs::read_to_string(manifest).expect("Failed to read the manifest");
+        for (index, explanation) in explainer::explain_rtm(&manifest, &network).iter().enumerate() {
+            println!("{}. {}", index + 1, explanation);
+        }
+        return;
+    }
+    if let Command::ResimScript { offered_amount, requested_amount } = &cli.command {
+        let script = resim::demo_script(
+            Decimal::from_str(offered_amount).expect("Invalid offered amount"),
+            Decimal::from_str(requested_amount).expect("Invalid requested amount")
+        );
+        let path = Path::new(&cli.out_dir).join(format!("{}.sh", cli.name.as_deref().unwrap_or("escrow_demo")));
+        std::fs::create_dir_all(&cli.out_dir).expect("Failed to create the output directory");
+        std::fs::write(&path, script).expect("Failed to write the script");
+        println!("Wrote {}", path.display());
+        return;
+    }
+    #[cfg(feature = "dry-run")]
+    if let Command::DryRun { manifest, fixture } = &cli.command {
+        dry_run(manifest, fixture, &network);
+        return;
+    }
+
+    let (manifest, name) = generate(&cli, &profile);
+    manifests::write_manifest(
+        manifest,
+        Path::new(&cli.out_dir),
+        &name,
+        &network,
+        &cli.formats
+    );
+}
+
+#[cfg(feature = "dry-run")]
+fn dry_run(manifest: &str, fixture: &str, network: &NetworkDefinition) {
+    use escrow_exercise_boilerplate::dry_run::{dry_run, Fixture};
+
+    let manifest = std::fs::read_to_string(manifest).expect("Failed to read the manifest");
+    let report = dry_run(&manifest, network, &Fixture::load(fixture));
+
+    if report.faucet_fee_added {
+        println!("The manifest locks no fee, the fee was locked from the faucet");
+    }
+    println!("{}", report.outcome);
+    println!();
+    println!("Fees (XRD):");
+    for (fee, amount) in &report.fees {
+        println!("  {}: {}", fee, amount);
+    }
+    println!();
+    println!("Balance changes:");
+    for (account, resource, change) in &report.balance_changes {
+        println!("  {} {} {}", account, resource, change);
+    }
+    if !report.success {
+        std::process::exit(1);
+    }
+}